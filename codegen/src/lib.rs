@@ -1,16 +1,50 @@
 use proc_macro::TokenStream;
-use quote::{ToTokens, format_ident, quote};
-use syn::{ItemFn, ItemStruct, parse_macro_input};
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{DeriveInput, ItemFn, ItemStruct, parse_macro_input};
 
-#[proc_macro_derive(Entity, attributes(id))]
+#[proc_macro_derive(
+    Entity,
+    attributes(id, expires_at, deleted, created_at, updated_at, validate)
+)]
 pub fn derive_entity(item: TokenStream) -> TokenStream {
-    let ItemStruct { ident, fields, .. } = parse_macro_input!(item as ItemStruct);
+    let ItemStruct {
+        attrs,
+        ident,
+        fields,
+        ..
+    } = parse_macro_input!(item as ItemStruct);
+
+    let mut validator = None;
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("validate")) {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("with") {
+                validator = Some(meta.value()?.parse::<syn::Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `validate` option, expected `with = path`"))
+            }
+        });
+
+        if let Err(error) = result {
+            return error.to_compile_error().into();
+        }
+    }
+
+    let validate = validator.map(|validator| {
+        quote! {
+            fn validate(&self) -> Result<(), String> {
+                #validator(self)
+            }
+        }
+    });
 
     let id_field = fields
         .iter()
         .enumerate()
         .find(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("id")))
-        .map(|(pos, f)| f.ident.clone().unwrap_or_else(|| format_ident!("{}", pos)));
+        .map(|(pos, f)| field_member(pos, f));
 
     let Some(id_field) = id_field else {
         return syn::Error::new_spanned(
@@ -21,93 +55,508 @@ pub fn derive_entity(item: TokenStream) -> TokenStream {
         .into();
     };
 
+    let expires_at = fields
+        .iter()
+        .enumerate()
+        .find(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("expires_at")))
+        .map(|(pos, f)| field_member(pos, f))
+        .map(|field| {
+            quote! {
+                fn expires_at(&self) -> Option<u64> {
+                    self.#field.into()
+                }
+            }
+        });
+
+    let deleted = fields
+        .iter()
+        .enumerate()
+        .find(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("deleted")))
+        .map(|(pos, f)| field_member(pos, f))
+        .map(|field| {
+            quote! {
+                const SOFT_DELETE: bool = true;
+
+                fn is_deleted(&self) -> bool {
+                    self.#field
+                }
+
+                fn set_deleted(&mut self, deleted: bool) {
+                    self.#field = deleted;
+                }
+            }
+        });
+
+    let created_at = fields
+        .iter()
+        .enumerate()
+        .find(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("created_at")))
+        .map(|(pos, f)| field_member(pos, f));
+
+    let updated_at = fields
+        .iter()
+        .enumerate()
+        .find(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("updated_at")))
+        .map(|(pos, f)| field_member(pos, f));
+
+    // The timestamps are converted with `From<u64>`, so `Option<u64>` fields work too
+    let set_created_at = created_at
+        .as_ref()
+        .map(|field| quote! { self.#field = now.into(); });
+    let set_updated_at = updated_at
+        .as_ref()
+        .map(|field| quote! { self.#field = now.into(); });
+
+    let on_insert = (created_at.is_some() || updated_at.is_some()).then(|| {
+        quote! {
+            fn on_insert(&mut self, now: u64) {
+                #set_created_at
+                #set_updated_at
+            }
+        }
+    });
+
+    let on_update = updated_at.is_some().then(|| {
+        quote! {
+            fn on_update(&mut self, now: u64) {
+                #set_updated_at
+            }
+        }
+    });
+
     quote! {
         impl whim::prelude::Entity for #ident {
             fn get_id(&self) -> &whim::prelude::Id<Self> {
                 &self.#id_field
             }
+
+            fn set_id(&mut self, id: whim::prelude::Id<Self>) {
+                self.#id_field = id;
+            }
+
+            #expires_at
+
+            #deleted
+
+            #on_insert
+
+            #on_update
+
+            #validate
         }
     }
     .into()
 }
 
+/// Returns how to access the field at the given position: by name, or by index for tuple structs.
+fn field_member(pos: usize, field: &syn::Field) -> syn::Member {
+    match &field.ident {
+        Some(ident) => syn::Member::Named(ident.clone()),
+        None => syn::Member::Unnamed(pos.into()),
+    }
+}
+
+/// Derives `Searchable` for a struct, using the fields marked with `#[search]`.
+/// On enums, the fields marked with `#[search]` in the active variant are used,
+/// so variants without any contribute nothing.
+///
+/// By default each field is indexed and scored on its own. A field can be given a weight
+/// and a label with `#[search(weight = 2.0, label = "title")]`, the label defaulting
+/// to the field name and the weight to `1.0`. Weights must be finite and non-negative. The score of the struct combines the weighted scores of the fields
+/// that matched with the `ScoreAggregation` of the search config, by default the best one,
+/// `max(weight * score)`, see `FieldScore::combine`.
+/// The n-gram size used to index a field can be tuned with `#[search(ngram = 2)]`,
+/// smaller n-grams suiting short fields better.
+///
+/// Adding `#[search(concat)]` on the struct instead joins every searchable field with a space
+/// and indexes the result as a single text, so a query can span several fields.
+/// This loses the per-field scoring: a short field matching perfectly is diluted by the others.
 #[proc_macro_derive(Searchable, attributes(search))]
 pub fn derive_searchable(item: TokenStream) -> TokenStream {
-    // TODO support for enums
-    let ItemStruct { ident, fields, .. } = parse_macro_input!(item as ItemStruct);
+    let DeriveInput {
+        attrs, ident, data, ..
+    } = parse_macro_input!(item as DeriveInput);
 
-    let supported_fields = fields
-        .iter()
-        .filter(|f| f.attrs.iter().any(|a| a.path().is_ident("search")))
-        .enumerate()
-        .map(|(pos, f)| f.ident.clone().unwrap_or_else(|| format_ident!("{}", pos)))
-        .collect::<Vec<_>>();
+    let mut concat = false;
 
-    let index_statements = supported_fields.iter().map(|field| {
-        quote! {
-            self.#field.index(indexer);
+    for attr in attrs.iter().filter(|a| a.path().is_ident("search")) {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("concat") {
+                concat = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `search` option, expected `concat`"))
+            }
+        });
+
+        if let Err(error) = result {
+            return error.to_compile_error().into();
+        }
+    }
+
+    let shape = match SearchShape::parse(data) {
+        Ok(shape) => shape,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    // Checks each searched field type on its own, so errors point at the offending field
+    let field_assertions = shape.fields().map(|SearchField { ty, .. }| {
+        quote_spanned! {ty.span()=>
+            assert_searchable::<#ty>();
         }
     });
 
-    let score_statements = supported_fields
-        .iter()
-        .map(|field| {
-            quote! {
-                self.#field.get_score(searcher)
+    let assertions = quote! {
+        const _: () = {
+            fn assert_searchable<T: ?Sized + whim::search::Searchable>() {}
+
+            fn assert_fields() {
+                #(#field_assertions)*
             }
-        })
-        .collect::<Vec<_>>();
+        };
+    };
 
-    let get_score = if score_statements.is_empty() {
+    let write_text_statements = shape.expand(|_, access| {
         quote! {
-            None
+            #access.write_text(buffer);
         }
-    } else {
-        quote! {
-            let items = vec![#(#score_statements),*]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<_>>();
+    });
 
-            if !items.is_empty() {
-                Some(items.into_iter().fold(0f32, f32::max))
-            } else {
-                None
+    if concat {
+        // The fields are indexed as a single text, so they cannot have their own n-gram size
+        if let Some(ngram) = shape.fields().find_map(|field| field.ngram.as_ref()) {
+            return syn::Error::new_spanned(ngram, "`ngram` cannot be used with `concat`")
+                .to_compile_error()
+                .into();
+        }
+
+        return quote! {
+            #assertions
+
+            impl whim::search::Searchable for #ident {
+                fn index(&self, indexer: &mut whim::search::NgramIndexer) {
+                    let mut text = String::new();
+                    self.write_text(&mut text);
+                    indexer.index(&text);
+                }
+
+                fn get_score(&self, searcher: &dyn whim::search::Scorer) -> Option<f32> {
+                    let mut text = String::new();
+                    self.write_text(&mut text);
+                    searcher.get_score(&text)
+                }
+
+                fn write_text(&self, buffer: &mut String) {
+                    #write_text_statements
+                }
             }
         }
-    };
+        .into();
+    }
+
+    let index_statements = shape.expand(|SearchField { ngram, .. }, access| match ngram {
+        Some(ngram) => quote! {
+            indexer.with_ngram_size(#ngram, |indexer| #access.index(indexer));
+        },
+        None => quote! {
+            #access.index(indexer);
+        },
+    });
+
+    let field_score_statements = shape.expand(|SearchField { weight, label, .. }, access| {
+        quote! {
+            if let Some(found) = #access.get_match(searcher) {
+                scores.push(whim::search::FieldScore {
+                    label: Some(#label),
+                    weight: #weight,
+                    score: found.score,
+                    range: found.range,
+                });
+            }
+        }
+    });
 
     quote! {
+        #assertions
+
         impl whim::search::Searchable for #ident {
             fn index(&self, indexer: &mut whim::search::NgramIndexer) {
-                #(#index_statements)*
+                #index_statements
+            }
+
+            fn get_score(&self, searcher: &dyn whim::search::Scorer) -> Option<f32> {
+                let mut scores = Vec::new();
+                self.get_field_scores(searcher, &mut scores);
+                whim::search::FieldScore::combine(&scores, searcher.aggregation())
+            }
+
+            fn get_field_scores(
+                &self,
+                searcher: &dyn whim::search::Scorer,
+                scores: &mut Vec<whim::search::FieldScore>,
+            ) {
+                #field_score_statements
             }
 
-            fn get_score(&self, searcher: &whim::search::BitapSearcher) -> Option<f32> {
-                #get_score
+            fn write_text(&self, buffer: &mut String) {
+                #write_text_statements
             }
         }
     }
     .into()
 }
 
+/// The searchable fields of a struct, or of each variant of an enum.
+enum SearchShape {
+    Struct(Vec<SearchField>),
+    Enum(Vec<(syn::Ident, Vec<SearchField>)>),
+}
+
+impl SearchShape {
+    /// Collects the fields marked with `#[search]` of the struct or of each enum variant.
+    fn parse(data: syn::Data) -> syn::Result<SearchShape> {
+        match data {
+            syn::Data::Struct(data) => {
+                Ok(SearchShape::Struct(SearchField::parse_all(&data.fields)?))
+            }
+            syn::Data::Enum(data) => data
+                .variants
+                .into_iter()
+                .map(|variant| Ok((variant.ident, SearchField::parse_all(&variant.fields)?)))
+                .collect::<syn::Result<_>>()
+                .map(SearchShape::Enum),
+            syn::Data::Union(data) => Err(syn::Error::new_spanned(
+                data.union_token,
+                "Searchable cannot be derived for unions",
+            )),
+        }
+    }
+
+    /// Iterates over every searchable field, across all variants for enums.
+    fn fields(&self) -> Box<dyn Iterator<Item = &SearchField> + '_> {
+        match self {
+            SearchShape::Struct(fields) => Box::new(fields.iter()),
+            SearchShape::Enum(variants) => {
+                Box::new(variants.iter().flat_map(|(_, fields)| fields.iter()))
+            }
+        }
+    }
+
+    /// Generates a statement for each searchable field with `statement`, given the field
+    /// and an expression accessing it. For enums, the statements of the active variant
+    /// are run in a `match` binding its searchable fields.
+    fn expand<F>(&self, statement: F) -> proc_macro2::TokenStream
+    where
+        F: Fn(&SearchField, proc_macro2::TokenStream) -> proc_macro2::TokenStream,
+    {
+        match self {
+            SearchShape::Struct(fields) => fields
+                .iter()
+                .map(|field| {
+                    let member = &field.member;
+                    statement(field, quote! { self.#member })
+                })
+                .collect(),
+            // An enum without variants has no value to match on
+            SearchShape::Enum(variants) if variants.is_empty() => quote! { match *self {} },
+            SearchShape::Enum(variants) => {
+                let arms = variants.iter().map(|(variant, fields)| {
+                    let members = fields.iter().map(|field| &field.member);
+                    let bindings = (0..fields.len())
+                        .map(|pos| format_ident!("__search_field_{}", pos))
+                        .collect::<Vec<_>>();
+                    let statements = fields
+                        .iter()
+                        .zip(&bindings)
+                        .map(|(field, binding)| statement(field, quote! { #binding }));
+
+                    // Brace patterns work for named, tuple and unit variants alike
+                    quote! {
+                        Self::#variant { #(#members: #bindings,)* .. } => {
+                            #(#statements)*
+                        }
+                    }
+                });
+
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A field marked with `#[search]`, along with its options.
+struct SearchField {
+    member: syn::Member,
+    ty: syn::Type,
+    weight: f32,
+    label: String,
+    ngram: Option<syn::LitInt>,
+}
+
+impl SearchField {
+    /// Collects the fields marked with `#[search]`, parsing their options.
+    fn parse_all(fields: &syn::Fields) -> syn::Result<Vec<SearchField>> {
+        let mut search_fields = Vec::new();
+
+        for (pos, field) in fields.iter().enumerate() {
+            let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("search")) else {
+                continue;
+            };
+
+            let member = field_member(pos, field);
+
+            let mut search_field = SearchField {
+                label: match &member {
+                    syn::Member::Named(ident) => ident.to_string(),
+                    syn::Member::Unnamed(index) => index.index.to_string(),
+                },
+                member,
+                ty: field.ty.clone(),
+                weight: 1.0,
+                ngram: None,
+            };
+
+            // A bare `#[search]` has no options
+            if let syn::Meta::List(_) = attr.meta {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("weight") {
+                        let weight = meta.value()?.parse::<syn::Lit>()?;
+
+                        search_field.weight = match &weight {
+                            syn::Lit::Float(weight) => weight.base10_parse()?,
+                            syn::Lit::Int(weight) => weight.base10_parse()?,
+                            _ => return Err(syn::Error::new_spanned(weight, "expected a number")),
+                        };
+
+                        if !search_field.weight.is_finite() || search_field.weight < 0.0 {
+                            return Err(syn::Error::new_spanned(
+                                weight,
+                                "`weight` must be a finite, non-negative number",
+                            ));
+                        }
+
+                        Ok(())
+                    } else if meta.path.is_ident("label") {
+                        search_field.label = meta.value()?.parse::<syn::LitStr>()?.value();
+                        Ok(())
+                    } else if meta.path.is_ident("ngram") {
+                        let ngram = meta.value()?.parse::<syn::LitInt>()?;
+
+                        if ngram.base10_parse::<usize>()? == 0 {
+                            return Err(syn::Error::new_spanned(
+                                ngram,
+                                "the n-gram size must be positive",
+                            ));
+                        }
+
+                        search_field.ngram = Some(ngram);
+                        Ok(())
+                    } else {
+                        Err(meta.error(
+                            "unsupported `search` option, expected `weight`, `label` or `ngram`",
+                        ))
+                    }
+                })?;
+            }
+
+            search_fields.push(search_field);
+        }
+
+        Ok(search_fields)
+    }
+}
+
 struct IndexerAttr {
     index_type: syn::Type,
     entity_type: syn::Type,
+    descending: bool,
+    /// The name the index is persisted under, when declared with `persist = "name"`
+    persist: Option<syn::LitStr>,
+    unique: bool,
 }
 
 impl syn::parse::Parse for IndexerAttr {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let index_type: syn::Type = input.parse()?;
-        input.parse::<syn::Token![->]>()?; // Expect a comma after the
+        input.parse::<syn::Token![->]>()?; // Expect an arrow between the key and entity types
         let entity_type: syn::Type = input.parse()?;
+
+        let mut descending = false;
+        let mut persist = None;
+        let mut unique = false;
+
+        // Optional comma-separated options after the types
+        while input.parse::<Option<syn::Token![,]>>()?.is_some() {
+            if input.is_empty() {
+                break;
+            }
+
+            let option: syn::Ident = input.parse()?;
+
+            if option == "order" {
+                input.parse::<syn::Token![=]>()?;
+                let order: syn::LitStr = input.parse()?;
+
+                descending = match order.value().as_str() {
+                    "asc" => false,
+                    "desc" => true,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            order,
+                            "Index order must be \"asc\" or \"desc\"",
+                        ));
+                    }
+                };
+            } else if option == "persist" {
+                if !cfg!(feature = "bincode") {
+                    return Err(syn::Error::new_spanned(
+                        option,
+                        "Persisting an index requires the `bincode` feature",
+                    ));
+                }
+
+                // An explicit name keeps persisted tables loadable across builds,
+                // unlike the type name of the index
+                if input.parse::<Option<syn::Token![=]>>()?.is_none() {
+                    return Err(syn::Error::new_spanned(
+                        option,
+                        "Persisted indices need a name: `persist = \"name\"`",
+                    ));
+                }
+
+                persist = Some(input.parse::<syn::LitStr>()?);
+            } else if option == "unique" {
+                unique = true;
+            } else {
+                return Err(syn::Error::new_spanned(option, "Unsupported index option"));
+            }
+        }
+
         Ok(IndexerAttr {
             index_type,
             entity_type,
+            descending,
+            persist,
+            unique,
         })
     }
 }
 
+/// Turns a key generator into an index, with `#[index(Key -> Entity)]`.
+///
+/// The function returns the key of an entry, an `Option` of it to leave the entry out,
+/// or a `Vec` of them to index the entry under several keys. Any `Ord` key type works,
+/// including tuples to index by several fields at once, e.g. `#[index((String, u64) -> Note)]`,
+/// looked up with `find(&(author, timestamp))` and ordered by the first field, then the next.
+/// Options can follow the types: `order = "desc"` to reverse the key order,
+/// `unique` to fail inserts and updates with `Error::UniqueViolation` when a key is already used
+/// by another entity (the key type must implement `Debug`),
+/// and `persist = "name"` to save the index along with the table under that name,
+/// which must stay the same for saved tables to restore it (requires the `bincode` feature).
 #[allow(clippy::cmp_owned)]
 #[proc_macro_attribute]
 pub fn index(attrs: TokenStream, item: TokenStream) -> TokenStream {
@@ -115,8 +564,62 @@ pub fn index(attrs: TokenStream, item: TokenStream) -> TokenStream {
     let IndexerAttr {
         index_type,
         entity_type,
+        descending,
+        persist,
+        unique,
     } = syn::parse_macro_input!(attrs as IndexerAttr);
 
+    let order = if descending {
+        quote! { whim::indices::KeyOrder::Descending }
+    } else {
+        quote! { whim::indices::KeyOrder::Ascending }
+    };
+
+    // Persisted indices store the IDs under each key, to be restored when the table is loaded
+    let persistence = if let Some(name) = persist {
+        quote! {
+            fn name(&self) -> &'static str {
+                #name
+            }
+
+            fn persist(&self) -> Result<Option<Vec<u8>>, whim::bincode::error::EncodeError> {
+                self.storage.encode_ids().map(Some)
+            }
+
+            fn restore(
+                &mut self,
+                bytes: &[u8],
+                entities: &std::collections::BTreeMap<
+                    whim::ids::Id<Self::Entity>,
+                    whim::tables::Entry<Self::Entity>,
+                >,
+            ) -> bool {
+                self.storage.decode_ids(bytes, entities).is_ok()
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Unique indices reject entities whose keys are already used by another entity
+    let uniqueness = if unique {
+        quote! {
+            fn check(&self, entity: &Entry<Self::Entity>) -> Result<(), whim::Error> {
+                let keys = self.generate_indicies(entity);
+
+                match self.storage.conflict(&keys, entity) {
+                    Some(key) => Err(whim::Error::UniqueViolation(
+                        format!("{:?}", key),
+                        std::any::type_name::<Self::Entity>(),
+                    )),
+                    None => Ok(()),
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let attributes = &function.attrs;
     let vis = &function.vis;
     let struct_name = &function.sig.ident;
@@ -176,14 +679,88 @@ pub fn index(attrs: TokenStream, item: TokenStream) -> TokenStream {
             ) -> Vec<&whim::tables::Entry<#entity_type>> {
                 self.storage.get(key)
             }
+
+            /// Finds the IDs of the entries with the given key.
+            pub fn ids(
+                &self,
+                key: &#index_type,
+            ) -> Vec<&whim::ids::Id<#entity_type>> {
+                self.storage.ids(key)
+            }
+
+            /// Counts the entries with the given key.
+            pub fn count(&self, key: &#index_type) -> usize {
+                self.storage.count(key)
+            }
+
+            /// Returns whether any entry has the given key.
+            pub fn contains_key(&self, key: &#index_type) -> bool {
+                self.storage.contains_key(key)
+            }
+
+            /// Finds the entry with the given key, for indices with a single entry per key.
+            /// If several entries share the key, the first one indexed is returned.
+            pub fn find_one(
+                &self,
+                key: &#index_type,
+            ) -> Option<&whim::tables::Entry<#entity_type>> {
+                self.storage.get_one(key)
+            }
+
+            /// Finds the entries under every key matching the predicate, without duplicates.
+            pub fn find_where<F: Fn(&#index_type) -> bool>(
+                &self,
+                predicate: F,
+            ) -> Vec<&whim::tables::Entry<#entity_type>> {
+                self.storage.find_where(predicate)
+            }
+
+            /// Finds the entries under every key within the range, following the index order,
+            /// without duplicates.
+            pub fn find_range<R: std::ops::RangeBounds<#index_type>>(
+                &self,
+                range: R,
+            ) -> Vec<&whim::tables::Entry<#entity_type>> {
+                self.storage.range(range)
+            }
+
+            /// Iterates over the distinct keys, following the index order.
+            pub fn keys(&self) -> impl Iterator<Item = &#index_type> {
+                self.storage.keys()
+            }
+
+            /// Iterates over the keys along with the entries under each of them,
+            /// following the index order.
+            pub fn groups(
+                &self,
+            ) -> impl Iterator<Item = (&#index_type, &Vec<whim::tables::Entry<#entity_type>>)> {
+                self.storage.iter()
+            }
+
+            /// Returns the entries under the first key, following the index order.
+            pub fn first(&self) -> Vec<&whim::tables::Entry<#entity_type>> {
+                self.storage.first()
+            }
+
+            /// Returns the entries under the last key, following the index order.
+            pub fn last(&self) -> Vec<&whim::tables::Entry<#entity_type>> {
+                self.storage.last()
+            }
+        }
+
+        impl whim::indices::KeyedIndexer for #struct_name {
+            type Key = #index_type;
+
+            fn storage(&self) -> &whim::indices::IndexStorage<Self::Key, Self::Entity> {
+                &self.storage
+            }
         }
 
         impl whim::indices::Indexer for #struct_name {
             type Entity = #entity_type;
 
             fn index(&mut self, entity: &Entry<Self::Entity>) {
-                let keys = self.generate_indicies(entity);
-                self.storage.push(keys, entity);
+                self.index_counted(entity);
             }
 
             fn forget(&mut self, entity: &Entry<Self::Entity>) {
@@ -191,18 +768,136 @@ pub fn index(attrs: TokenStream, item: TokenStream) -> TokenStream {
                 self.storage.forget(keys, entity);
             }
 
+            fn clear(&mut self) {
+                self.storage.clear();
+            }
+
             fn as_any(&self) -> &dyn std::any::Any {
                 self
             }
+
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+
+            fn index_counted(&mut self, entity: &Entry<Self::Entity>) -> usize {
+                let keys = self.generate_indicies(entity);
+                self.storage.push(keys, entity)
+            }
+
+            #persistence
+
+            #uniqueness
         }
 
         impl Default for #struct_name {
             fn default() -> Self {
                 Self {
-                    storage: whim::indices::IndexStorage::default(),
+                    storage: whim::indices::IndexStorage::with_order(#order),
                 }
             }
         }
     }
     .into()
 }
+
+/// Derives helpers over a struct whose named fields are all tables:
+/// `clear_all` to empty every table, and with the `bincode` feature,
+/// `save_to_path` and `load_from_path` to save each table to `<field>.bin` in a directory,
+/// which requires the entities of every table to implement `Encode` and `Decode`.
+/// `#[database(on_load = path)]` runs `path(&mut database)` after loading,
+/// to add back the indices of the tables, restoring the ones declared with `persist`.
+#[proc_macro_derive(Database, attributes(database))]
+pub fn derive_database(item: TokenStream) -> TokenStream {
+    let ItemStruct {
+        attrs,
+        ident,
+        fields,
+        ..
+    } = parse_macro_input!(item as ItemStruct);
+
+    let mut on_load = None;
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("database")) {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("on_load") {
+                on_load = Some(meta.value()?.parse::<syn::Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `database` option, expected `on_load = path`"))
+            }
+        });
+
+        if let Err(error) = result {
+            return error.to_compile_error().into();
+        }
+    }
+
+    let syn::Fields::Named(fields) = fields else {
+        return syn::Error::new_spanned(fields, "Database must be a struct with named fields")
+            .to_compile_error()
+            .into();
+    };
+
+    let names = fields
+        .named
+        .iter()
+        .filter_map(|field| field.ident.as_ref())
+        .collect::<Vec<_>>();
+
+    let persistence = cfg!(feature = "bincode").then(|| {
+        let files = names.iter().map(|name| format!("{name}.bin"));
+        let load_files = files.clone();
+        let mutability = on_load.is_some().then(|| quote! { mut });
+        let on_load = on_load.map(|on_load| quote! { #on_load(&mut database); });
+
+        quote! {
+            /// Saves every table to `<field>.bin` in the directory, creating it if needed.
+            pub fn save_to_path<P: AsRef<std::path::Path>>(
+                &self,
+                dir: P,
+            ) -> Result<(), whim::SnapshotError> {
+                let dir = dir.as_ref();
+                std::fs::create_dir_all(dir)?;
+
+                #(self.#names.save_to_path(dir.join(#files))?;)*
+
+                Ok(())
+            }
+
+            /// Loads every table from `<field>.bin` in the directory,
+            /// starting with an empty table when its file does not exist.
+            pub fn load_from_path<P: AsRef<std::path::Path>>(
+                dir: P,
+            ) -> Result<Self, whim::SnapshotError> {
+                let dir = dir.as_ref();
+
+                let #mutability database = Self {
+                    #(#names: match whim::tables::Table::load_from_path(dir.join(#load_files)) {
+                        Ok(table) => table,
+                        Err(whim::SnapshotError::NotFound(_)) => Default::default(),
+                        Err(error) => return Err(error),
+                    },)*
+                };
+
+                #on_load
+
+                Ok(database)
+            }
+        }
+    });
+
+    quote! {
+        impl #ident {
+            /// Removes every entity from every table, keeping their indices and configuration.
+            /// Stops at the first table whose storage fails.
+            pub fn clear_all(&mut self) -> Result<(), whim::Error> {
+                #(self.#names.clear()?;)*
+                Ok(())
+            }
+
+            #persistence
+        }
+    }
+    .into()
+}
@@ -1,7 +1,8 @@
 //! Run this example with `cargo run --example bincode --features bincode`.
-//! This example will try to load a database from a file named `db.bin`, or create a new one if it doesn't exist.
-//! It will then push a new note and user into the database and save it back to the file.
-//! Note: currently indices are not saved, so you need to re-add them after loading the database.
+//! This example will try to load the tables from the files `notes.bin` and `users.bin`, or create new ones if they don't exist.
+//! It will then push a new note and user into the tables and save them back to the files.
+//! Note: indices are only saved when declared with `#[index(..., persist = "name")]`, and are restored
+//! rather than rebuilt when re-added after loading the database, e.g. in `on_load`. Other indices are rebuilt.
 
 #![allow(dead_code)]
 
@@ -26,26 +27,42 @@ struct User {
     name: String,
 }
 
-#[derive(Default, Encode, Decode)]
+#[index(String -> Note, persist = "notes_by_author")]
+fn NoteCreatedByIndex(note: &Entry<Note>) -> String {
+    note.created_by.value().to_string()
+}
+
+/// Each table is saved to its own file, named after the field.
+#[derive(Database)]
+#[database(on_load = Database::add_indices)]
 struct Database {
     notes: Table<Note>,
     users: Table<User>,
 }
 
+impl Database {
+    fn add_indices(&mut self) {
+        self.notes
+            .add_index(NoteCreatedByIndex::default())
+            .expect("Failed to add the index");
+    }
+}
+
 fn main() {
     // Used for unique IDs
     let time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards");
 
-    let mut db = load_database();
+    let mut database = Database::load_from_path(".").expect("Failed to load the database");
+    let Database { notes, users } = &mut database;
 
     let user = User {
         id: Id::new(time.as_millis().to_string()),
         name: "John".to_string(),
     };
 
-    let user = db.users.insert(user).expect("Failed to insert user");
+    let user = users.insert(user).expect("Failed to insert user");
 
     let note = Note {
         id: Id::new(time.as_millis().to_string()),
@@ -55,35 +72,22 @@ fn main() {
         created_by: user.get_id().clone(),
     };
 
-    db.notes.insert(note).expect("Failed to insert note");
+    notes.insert(note).expect("Failed to insert note");
+
+    // Print all the notes, following `created_by` to the user who wrote each of them
+    for entry in notes.iter() {
+        let author = entry
+            .created_by
+            .resolve(users)
+            .map_or("unknown", |user| user.name.as_str());
 
-    // Print all the notes
-    for entry in db.notes.iter() {
         println!(
-            "Note ID: {}, Title: {}, Created At: {}, Created By: {}",
-            entry.id, entry.title, entry.created_at, entry.created_by,
+            "Note ID: {}, Title: {}, Created At: {}, Created By: {} ({})",
+            entry.id, entry.title, entry.created_at, author, entry.created_by,
         );
     }
 
-    save_database(&db);
-}
-
-fn load_database() -> Database {
-    match std::fs::read("db.bin") {
-        Ok(data) => match bincode::decode_from_slice(&data, bincode::config::standard()) {
-            Ok((db, _)) => db,
-            Err(e) => {
-                eprintln!("Failed to decode database: {e}");
-                Database::default()
-            }
-        },
-        Err(_) => Database::default(),
-    }
-}
-
-fn save_database(db: &Database) {
-    let encoded =
-        bincode::encode_to_vec(db, bincode::config::standard()).expect("Failed to encode database");
-
-    std::fs::write("db.bin", encoded).expect("Failed to write database to file");
+    database
+        .save_to_path(".")
+        .expect("Failed to save the database");
 }
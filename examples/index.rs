@@ -4,6 +4,7 @@
 //! - `NoteCreatedAtIndex` which indexes notes by their creation time.
 //! - `NoteCreatedByIndex` which indexes notes by the user who created them.
 //! - `NoteTitleWordsIndex` which indexes notes by the words in their title.
+//! - `NoteAuthorTimeIndex` which indexes notes by their author and creation time together.
 
 #![allow(dead_code)]
 
@@ -33,20 +34,34 @@ fn NoteCreatedByIndex(note: &Entry<Note>) -> Option<String> {
 
 /// Finally, indices can also return `Vec<T>` types, where `T` is the type of the index key.
 /// This allows for multiple keys to be associated with a single entry.
+/// Here `tokenize` splits the title into lowercase words, stripping punctuation.
 #[index(String -> Note)]
 fn NoteTitleWordsIndex(note: &Entry<Note>) -> Vec<String> {
-    note.title
-        .split_whitespace()
-        .map(|word| word.to_string())
-        .collect()
+    whim::search::tokenize(&note.title)
+}
+
+/// Tuples can be used as keys to index by several fields at once,
+/// ordering the keys by the first field, then by the next ones.
+#[index((String, u64) -> Note)]
+fn NoteAuthorTimeIndex(note: &Entry<Note>) -> Option<(String, u64)> {
+    Some((note.created_by.clone()?, note.created_at))
 }
 
 fn main() {
     let mut table = Table::<Note>::default();
 
-    table.add_index(NoteCreatedAtIndex::default());
-    table.add_index(NoteCreatedByIndex::default());
-    table.add_index(NoteTitleWordsIndex::default());
+    table
+        .add_index(NoteCreatedAtIndex::default())
+        .expect("Failed to add the index");
+    table
+        .add_index(NoteCreatedByIndex::default())
+        .expect("Failed to add the index");
+    table
+        .add_index(NoteTitleWordsIndex::default())
+        .expect("Failed to add the index");
+    table
+        .add_index(NoteAuthorTimeIndex::default())
+        .expect("Failed to add the index");
 
     table
         .insert(Note {
@@ -108,9 +123,22 @@ fn main() {
     let results = table
         .get_index::<NoteTitleWordsIndex>()
         .unwrap()
-        .find(&"Note".to_string());
+        .find(&"note".to_string());
 
     for entry in results {
         println!("Found note: {} with title containing 'Note'", entry.title);
     }
+
+    // Should print the third note
+    let results = table
+        .get_index::<NoteAuthorTimeIndex>()
+        .unwrap()
+        .find(&("user1".to_string(), 1751007261));
+
+    for entry in results {
+        println!(
+            "Found note: {} created by: {:?} at: {}",
+            entry.title, entry.created_by, entry.created_at
+        );
+    }
 }
@@ -10,7 +10,8 @@ use whim::prelude::*;
 struct Note {
     #[id]
     id: Id<Self>,
-    #[search]
+    /// A match in the title counts twice as much as one in the content.
+    #[search(weight = 2.0)]
     title: String,
     #[search]
     content: String,
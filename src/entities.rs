@@ -3,5 +3,51 @@ use crate::ids::Id;
 /// A trait representing an entity in a database.
 /// An entity is a record that can be stored in a table.
 pub trait Entity {
+    /// Whether the entity can be soft-deleted, see [`Table::soft_delete`](crate::tables::Table::soft_delete).
+    /// With the derive, this is `true` when a field is marked with `#[deleted]`.
+    const SOFT_DELETE: bool = false;
+
     fn get_id(&self) -> &Id<Self>;
+
+    /// Replaces the ID of the entity, used by the table to assign generated IDs.
+    /// With the derive, this writes the field marked with `#[id]`.
+    fn set_id(&mut self, id: Id<Self>);
+
+    /// Checks the invariants of the entity, called by the table before inserting or updating it.
+    /// With the derive, this calls the function given with `#[validate(with = path)]`.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Returns the timestamp at which the entity expires, if any.
+    /// With the derive, this is read from the field marked with `#[expires_at]`.
+    fn expires_at(&self) -> Option<u64> {
+        None
+    }
+
+    /// Called by the table before storing a new entity, with the current time of its clock
+    /// (see [`TableConfig::clock`](crate::tables::TableConfig::clock)).
+    /// With the derive, this sets the fields marked with `#[created_at]` and `#[updated_at]`.
+    fn on_insert(&mut self, _now: u64) {}
+
+    /// Called by the table before storing an updated entity, with the current time of its clock.
+    /// With the derive, this sets the field marked with `#[updated_at]`.
+    fn on_update(&mut self, _now: u64) {}
+
+    /// Returns whether the entity is soft-deleted, see [`Table::soft_delete`](crate::tables::Table::soft_delete).
+    /// With the derive, this is read from the `bool` field marked with `#[deleted]`.
+    fn is_deleted(&self) -> bool {
+        false
+    }
+
+    /// Marks the entity as soft-deleted or not.
+    /// With the derive, this writes the field marked with `#[deleted]`; otherwise it does nothing,
+    /// and [`Entity::SOFT_DELETE`] is `false`.
+    fn set_deleted(&mut self, _deleted: bool) {}
+
+    /// Returns whether the entity is expired at the given timestamp.
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
 }
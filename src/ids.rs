@@ -1,5 +1,8 @@
 use crate::entities::Entity;
-use crate::search::{BitapSearcher, NgramIndexer, Searchable};
+use crate::search::searchable::push_text;
+use crate::search::{BitapMatch, NgramIndexer, Scorer, Searchable};
+use crate::storage::Storage;
+use crate::tables::{Entry, Table};
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
@@ -19,18 +22,39 @@ impl<T: Entity + ?Sized> Id<T> {
         }
     }
 
-    /// Generates a new [`Id`] with a random Ulid.
+    /// Generates a new [`Id`] with a random Ulid, e.g. `Id::<Note>::new_ulid()`.
     #[cfg(feature = "ulid")]
     pub fn new_ulid() -> Self {
         Self::new(ulid::Ulid::new().to_string())
     }
 
+    /// Generates a new [`Id`] with a random (version 4) Uuid, e.g. `Id::<Note>::new_uuid()`.
+    #[cfg(feature = "uuid")]
+    pub fn new_uuid() -> Self {
+        Self::new(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Creates a new [`Id`] from a Uuid string, failing if it is malformed.
+    /// Any accepted Uuid format is stored in its lowercase hyphenated form.
+    #[cfg(feature = "uuid")]
+    pub fn parse_uuid(value: &str) -> Result<Self, uuid::Error> {
+        uuid::Uuid::parse_str(value).map(|uuid| Self::new(uuid.to_string()))
+    }
+
     /// Returns the string value of the ID.
     pub fn value(&self) -> &str {
         &self.value
     }
 }
 
+impl<T: Entity + 'static> Id<T> {
+    /// Follows the ID to its entry in the given table, soft-deleted or not, like [`Table::find`].
+    /// IDs stored in other entities act as foreign keys, e.g. `note.created_by.resolve(&users)`.
+    pub fn resolve<'a, S: Storage<T>>(&self, table: &'a Table<T, S>) -> Option<&'a Entry<T>> {
+        table.find(self)
+    }
+}
+
 impl<T: Entity + ?Sized> Debug for Id<T> {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         let Id { value, _marker } = self;
@@ -85,9 +109,17 @@ impl<T: Entity + ?Sized> Searchable for Id<T> {
         indexer.index(self.value());
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
         searcher.get_score(self.value())
     }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        searcher.find(self.value())
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, self.value());
+    }
 }
 
 impl<T: Entity + ?Sized> Display for Id<T> {
@@ -1,35 +1,127 @@
+use crate::Error;
+use crate::ids::Id;
 use crate::prelude::Entity;
 use crate::tables::Entry;
 use std::any::Any;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::RangeBounds;
 
 pub trait Indexer: Any {
     type Entity: Entity;
 
     fn index(&mut self, entity: &Entry<Self::Entity>);
     fn forget(&mut self, entity: &Entry<Self::Entity>);
+    /// Removes every entry from the index, to index them again from scratch.
+    fn clear(&mut self);
     fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Indexes the entry like [`Indexer::index`], returning the number of keys it was indexed
+    /// under, as reported by [`Table::insert_detailed`](crate::tables::Table::insert_detailed).
+    /// Reports a single key by default; implemented by the `#[index]` macro.
+    fn index_counted(&mut self, entity: &Entry<Self::Entity>) -> usize {
+        self.index(entity);
+        1
+    }
+
+    /// Checks that the entry can be indexed, called before the table stores it.
+    /// Implemented by the `#[index(..., unique)]` macro to reject keys used by another entity.
+    fn check(&self, _entity: &Entry<Self::Entity>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Returns the name under which the index is persisted along with its table.
+    /// Defaults to the type name, which is not stable across builds, so indices implementing
+    /// [`Indexer::persist`] should return a fixed name, as `#[index(..., persist = "name")]` does.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Encodes the index to be persisted along with its table,
+    /// or returns `None` to have it rebuilt when the table is loaded.
+    /// Implemented by the `#[index(..., persist = "name")]` macro.
+    #[cfg(feature = "bincode")]
+    fn persist(&self) -> Result<Option<Vec<u8>>, bincode::error::EncodeError> {
+        Ok(None)
+    }
+
+    /// Restores the index from the bytes returned by [`Indexer::persist`],
+    /// looking up its entries in `entities`. Returns `false` if the index must be rebuilt instead.
+    #[cfg(feature = "bincode")]
+    fn restore(
+        &mut self,
+        _bytes: &[u8],
+        _entities: &BTreeMap<Id<Self::Entity>, Entry<Self::Entity>>,
+    ) -> bool {
+        false
+    }
+}
+
+/// An indexer storing entries by key in an [`IndexStorage`].
+/// Implemented by the structs generated with the `#[index]` macro.
+pub trait KeyedIndexer: Indexer {
+    type Key: Ord;
+
+    fn storage(&self) -> &IndexStorage<Self::Key, Self::Entity>;
+}
+
+/// The order in which an [`IndexStorage`] returns its keys.
+/// For orderings other than the natural or reversed one of the key, use a key type with a custom `Ord`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub enum KeyOrder {
+    /// Keys are returned following their `Ord` implementation.
+    #[default]
+    Ascending,
+    /// Keys are returned in reverse of their `Ord` implementation.
+    Descending,
 }
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct IndexStorage<K: Ord, E> {
     data: BTreeMap<K, Vec<Entry<E>>>,
+    order: KeyOrder,
 }
 
 impl<K: Ord, E> Default for IndexStorage<K, E> {
     fn default() -> Self {
+        Self::with_order(KeyOrder::default())
+    }
+}
+
+impl<K: Ord, E> IndexStorage<K, E> {
+    /// Creates an empty storage returning its keys in the given order.
+    pub fn with_order(order: KeyOrder) -> Self {
         IndexStorage {
             data: BTreeMap::new(),
+            order,
         }
     }
-}
 
-impl<K: Ord, E> IndexStorage<K, E> {
-    pub fn push(&mut self, keys: Vec<K>, entity: &Entry<E>) {
+    /// Removes every key and entry from the storage.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns the order in which keys are returned.
+    pub fn order(&self) -> KeyOrder {
+        self.order
+    }
+
+    /// Adds the entity under each of the given keys, returning the number of distinct keys.
+    /// Duplicate keys are ignored, so an entity appears at most once per key.
+    pub fn push(&mut self, mut keys: Vec<K>, entity: &Entry<E>) -> usize {
+        keys.sort();
+        keys.dedup();
+
+        let count = keys.len();
+
         for key in keys {
             self.data.entry(key).or_default().push(entity.clone());
         }
+
+        count
     }
 
     pub fn forget(&mut self, keys: Vec<K>, entity: &Entry<E>)
@@ -53,10 +145,190 @@ impl<K: Ord, E> IndexStorage<K, E> {
         }
     }
 
+    /// Returns the first of the given keys already holding an entity other than `entity`,
+    /// to keep the keys of unique indices from being shared.
+    pub fn conflict<'a>(&self, keys: &'a [K], entity: &Entry<E>) -> Option<&'a K>
+    where
+        E: Entity,
+    {
+        keys.iter().find(|key| {
+            self.data.get(key).is_some_and(|entries| {
+                entries
+                    .iter()
+                    .any(|entry| entry.get_id() != entity.get_id())
+            })
+        })
+    }
+
+    /// Returns the number of entries under the given key, without collecting them.
+    pub fn count(&self, key: &K) -> usize {
+        self.data.get(key).map_or(0, Vec::len)
+    }
+
+    /// Returns whether any entry is stored under the given key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.data.contains_key(key)
+    }
+
     pub fn get(&self, key: &K) -> Vec<&Entry<E>> {
         self.data
             .get(key)
             .map(|entries| entries.iter().collect())
             .unwrap_or_default()
     }
+
+    /// Returns the IDs of the entries under the given key, without cloning entries.
+    pub fn ids(&self, key: &K) -> Vec<&Id<E>>
+    where
+        E: Entity,
+    {
+        self.data
+            .get(key)
+            .map(|entries| entries.iter().map(|entry| entry.get_id()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_one(&self, key: &K) -> Option<&Entry<E>> {
+        self.data.get(key).and_then(|entries| entries.first())
+    }
+
+    /// Returns the entries under every key matching the predicate, following the storage order.
+    /// An entry indexed under several matching keys is only returned once.
+    pub fn find_where<F: Fn(&K) -> bool>(&self, predicate: F) -> Vec<&Entry<E>>
+    where
+        E: Entity,
+    {
+        let mut seen = HashSet::new();
+
+        self.ordered()
+            .filter(|(key, _)| predicate(key))
+            .flat_map(|(_, entries)| entries)
+            .filter(|entry| seen.insert(entry.get_id()))
+            .collect()
+    }
+
+    /// Returns the entries under every key within the range, following the storage order.
+    /// An entry indexed under several keys in the range is only returned once.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<&Entry<E>>
+    where
+        E: Entity,
+    {
+        let mut seen = HashSet::new();
+        let entries = self.data.range(range);
+
+        let entries: Box<dyn Iterator<Item = (&K, &Vec<Entry<E>>)>> = match self.order {
+            KeyOrder::Ascending => Box::new(entries),
+            KeyOrder::Descending => Box::new(entries.rev()),
+        };
+
+        entries
+            .flat_map(|(_, entries)| entries)
+            .filter(|entry| seen.insert(entry.get_id()))
+            .collect()
+    }
+
+    /// Iterates over the distinct keys, following the storage order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.ordered().map(|(key, _)| key)
+    }
+
+    /// Iterates over the keys along with the entries under each of them, following the storage order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &Vec<Entry<E>>)> {
+        self.ordered()
+    }
+
+    /// Iterates over the keys and their entries, following the storage order.
+    fn ordered(&self) -> Box<dyn Iterator<Item = (&K, &Vec<Entry<E>>)> + '_> {
+        match self.order {
+            KeyOrder::Ascending => Box::new(self.data.iter()),
+            KeyOrder::Descending => Box::new(self.data.iter().rev()),
+        }
+    }
+
+    /// Returns the entries under the first key, following the storage order.
+    pub fn first(&self) -> Vec<&Entry<E>> {
+        let entries = match self.order {
+            KeyOrder::Ascending => self.data.values().next(),
+            KeyOrder::Descending => self.data.values().next_back(),
+        };
+
+        entries
+            .map(|entries| entries.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the entries under the last key, following the storage order.
+    pub fn last(&self) -> Vec<&Entry<E>> {
+        let entries = match self.order {
+            KeyOrder::Ascending => self.data.values().next_back(),
+            KeyOrder::Descending => self.data.values().next(),
+        };
+
+        entries
+            .map(|entries| entries.iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<K: Ord, E: Entity> IndexStorage<K, E> {
+    /// Encodes the keys along with the IDs of their entries, without the entities themselves.
+    pub fn encode_ids(&self) -> Result<Vec<u8>, bincode::error::EncodeError>
+    where
+        K: bincode::Encode,
+    {
+        let ids = self
+            .data
+            .iter()
+            .map(|(key, entries)| {
+                (
+                    key,
+                    entries
+                        .iter()
+                        .map(|entry| entry.get_id())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        bincode::encode_to_vec(ids, bincode::config::standard())
+    }
+
+    /// Restores the storage from the bytes returned by [`IndexStorage::encode_ids`],
+    /// looking up the entries in `entities`.
+    /// Fails without changing the storage if an ID is missing from `entities`.
+    pub fn decode_ids(
+        &mut self,
+        bytes: &[u8],
+        entities: &BTreeMap<Id<E>, Entry<E>>,
+    ) -> Result<(), bincode::error::DecodeError>
+    where
+        K: bincode::Decode<()>,
+    {
+        let (ids, _) = bincode::decode_from_slice::<Vec<(K, Vec<Id<E>>)>, _>(
+            bytes,
+            bincode::config::standard(),
+        )?;
+
+        let mut data = BTreeMap::new();
+
+        for (key, ids) in ids {
+            let entries = ids
+                .iter()
+                .map(|id| {
+                    entities
+                        .get(id)
+                        .cloned()
+                        .ok_or(bincode::error::DecodeError::Other(
+                            "index refers to a missing entity",
+                        ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            data.insert(key, entries);
+        }
+
+        self.data = data;
+        Ok(())
+    }
 }
@@ -1,25 +1,138 @@
+//! Without the default `std` feature, only the search algorithms in [`search`] are available,
+//! requiring nothing but `alloc`. Tables, IDs and indices need `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use crate::entities::Entity;
+#[cfg(feature = "std")]
+use crate::ids::Id;
+#[cfg(feature = "std")]
 use thiserror::Error;
 
+#[cfg(feature = "std")]
 pub mod entities;
+#[cfg(feature = "std")]
 pub mod ids;
+#[cfg(feature = "std")]
 pub mod indices;
 pub mod search;
+#[cfg(feature = "std")]
+pub mod storage;
+#[cfg(feature = "std")]
 pub mod tables;
 
+/// Re-exported for the code generated by `#[index(..., persist = "name")]`.
+#[cfg(feature = "bincode")]
+#[doc(hidden)]
+pub use bincode;
+
+#[cfg(feature = "std")]
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     #[error("Tried to insert row with existing ID: `{0}` for entity `{1}`")]
     EntityAlreadyExists(String, &'static str),
     #[error("Entity not found with ID: `{0}` for entity `{1}`")]
     EntityNotFound(String, &'static str),
+    #[error("Validation failed for entity `{1}`: {0}")]
+    Validation(String, &'static str),
+    #[error("Entity ID changed from `{0}` to `{1}` for entity `{2}`")]
+    IdChanged(String, String, &'static str),
+    #[error("Tried to insert rows with existing or repeated IDs: {0:?} for entity `{1}`")]
+    DuplicateIds(Vec<String>, &'static str),
+    #[error(
+        "Tried to store row with a key already used by another row in a unique index: `{0}` for entity `{1}`"
+    )]
+    UniqueViolation(String, &'static str),
+    #[error("Tried to delete row still referenced by rows of `{1}`: `{0}` for entity `{2}`")]
+    ReferentialIntegrity(String, &'static str, &'static str),
+    #[error("Tried to soft-delete row of entity `{0}`, which has no `#[deleted]` field")]
+    SoftDeleteUnsupported(&'static str),
+    #[error("Storage failed for entity `{1}`: {0}")]
+    Storage(String, &'static str),
+}
+
+/// Why a table could not be saved or loaded with the `bincode` feature,
+/// see [`Table::save_to_path`](crate::tables::Table::save_to_path).
+#[cfg(feature = "bincode")]
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("No table saved at `{0}`")]
+    NotFound(std::path::PathBuf),
+    #[error("Failed to read or write the table: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to encode the table: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+    #[error("Failed to decode the table: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// Returns the ID of the entity the error is about, if any.
+    /// For [`Error::IdChanged`], this is the original ID,
+    /// and for [`Error::DuplicateIds`], the first duplicate ID.
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Error::EntityAlreadyExists(id, _)
+            | Error::EntityNotFound(id, _)
+            | Error::IdChanged(id, _, _)
+            | Error::ReferentialIntegrity(id, _, _) => Some(id),
+            Error::DuplicateIds(ids, _) => ids.first().map(String::as_str),
+            Error::Validation(_, _)
+            | Error::UniqueViolation(_, _)
+            | Error::SoftDeleteUnsupported(_)
+            | Error::Storage(_, _) => None,
+        }
+    }
+
+    /// Returns the type name of the entity the error is about.
+    pub fn entity_type(&self) -> &'static str {
+        match self {
+            Error::EntityAlreadyExists(_, entity_type)
+            | Error::EntityNotFound(_, entity_type)
+            | Error::Validation(_, entity_type)
+            | Error::IdChanged(_, _, entity_type)
+            | Error::DuplicateIds(_, entity_type)
+            | Error::UniqueViolation(_, entity_type)
+            | Error::ReferentialIntegrity(_, _, entity_type)
+            | Error::SoftDeleteUnsupported(entity_type)
+            | Error::Storage(_, entity_type) => entity_type,
+        }
+    }
+
+    /// Returns the ID of the entity the error is about, typed as an ID of `T`.
+    /// Returns `None` if the error has no ID or is about another entity type.
+    pub fn typed_id<T: Entity>(&self) -> Option<Id<T>> {
+        if self.entity_type() != std::any::type_name::<T>() {
+            return None;
+        }
+
+        self.id().map(Id::new)
+    }
 }
 
 pub mod prelude {
+    #[cfg(feature = "std")]
     pub use crate::Error;
+    #[cfg(feature = "std")]
     pub use crate::entities::Entity;
+    #[cfg(feature = "std")]
     pub use crate::ids::Id;
-    pub use crate::tables::{Entry, Table};
+    pub use crate::search::{SearchConfig, Searchable};
+    #[cfg(feature = "std")]
+    pub use crate::search::{SearchEngine, SearchResult};
+    #[cfg(feature = "std")]
+    pub use crate::tables::{
+        ChangeEvent, Entry, EntryMeta, MergePolicy, OnDelete, Table, TableConfig,
+    };
+    #[cfg(feature = "std")]
+    pub use codegen::Database;
+    #[cfg(feature = "std")]
     pub use codegen::Entity;
     pub use codegen::Searchable;
+    #[cfg(feature = "std")]
     pub use codegen::index;
 }
@@ -1,30 +1,122 @@
+use crate::search::{Normalizer, QueryIssue, ScoreAggregation, SearchConfig};
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// Where and how well a pattern matched a text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitapMatch {
+    /// The score of the match, as returned by [`Scorer::get_score`](crate::search::Scorer::get_score).
+    pub score: f32,
+    /// The byte range of the matched window in the original text, for highlighting.
+    /// `None` when the matched text is not known, for types only providing a score.
+    pub range: Option<Range<usize>>,
+    /// The number of mismatched characters in the matched window.
+    /// `None` when the matched text is not known, like `range`, or with scorers not counting mismatches.
+    pub mismatches: Option<usize>,
+}
+
 /// We use the Bitap algorithm for fuzzy searching.
 #[derive(Debug)]
 pub struct BitapSearcher<'a> {
-    /// The pattern to search for, in lowercase.
-    pub(crate) pattern: &'a str,
+    /// The pattern to search for, already normalized.
+    pattern: &'a str,
     /// A mask for each character in the pattern, used to track mismatches.
-    pub(crate) pattern_mask: [u32; 1024],
-    /// The maximum number of mismatches allowed for a match to be considered valid.
-    pub(crate) max_mismatches: usize,
+    /// Each mask spans `words` words, so patterns of any length are supported,
+    /// and characters missing from the pattern have no mask.
+    pattern_mask: HashMap<char, Vec<u64>>,
+    /// The number of words in the mask of a character.
+    words: usize,
+    /// The maximum number of mismatches allowed for a match to be considered valid,
+    /// always leaving at least one matching character.
+    max_mismatches: usize,
+    /// How much a match is penalized for starting late in the text, between 0 and 1.
+    position_weight: f32,
+    /// Added to the score of exact matches.
+    exact_match_boost: f32,
+    /// The normalization applied to texts, the same one used for the pattern.
+    normalizer: Normalizer,
+    /// How the scores of several fields or items are combined.
+    aggregation: ScoreAggregation,
 }
 
 impl<'a> BitapSearcher<'a> {
+    /// Creates a searcher for the given pattern, which must already be normalized
+    /// with the normalizer of `config`.
+    /// Fails if the pattern is empty.
+    pub fn new(pattern: &'a str, config: &SearchConfig) -> Result<Self, QueryIssue> {
+        if pattern.is_empty() {
+            return Err(QueryIssue::Empty);
+        }
+
+        let pattern_len = pattern.chars().count();
+        let words = pattern_len.div_ceil(u64::BITS as usize);
+        let mut pattern_mask = HashMap::new();
+
+        for (i, ch) in pattern.chars().enumerate() {
+            let mask = pattern_mask.entry(ch).or_insert_with(|| vec![0u64; words]);
+            mask[i / u64::BITS as usize] |= 1 << (i % u64::BITS as usize);
+        }
+
+        Ok(BitapSearcher {
+            pattern,
+            pattern_mask,
+            words,
+            max_mismatches: config.max_distance.min(pattern_len - 1),
+            position_weight: config.position_weight,
+            exact_match_boost: config.exact_match_boost,
+            normalizer: config.normalizer,
+            aggregation: config.aggregation,
+        })
+    }
+
+    /// Returns how the scores of several fields or items are combined,
+    /// for [`Searchable`](crate::search::Searchable) implementations scoring more than one text.
+    pub fn aggregation(&self) -> ScoreAggregation {
+        self.aggregation
+    }
+
     /// Calculates a score for the given text based on the pattern.
+    /// The window with the fewest mismatches is kept, the earliest one on ties,
+    /// and its score is lowered by `position_weight` the further it is in the text.
+    /// Texts containing the pattern exactly get `exact_match_boost` added to their score.
     /// Returns None if every segment tested has more mismatches than allowed.
     pub fn get_score(&self, text: &str) -> Option<f32> {
-        let text = text.to_lowercase();
+        self.find(text).map(|found| found.score)
+    }
+
+    /// Finds the best match of the pattern in the given text, scored like [`BitapSearcher::get_score`],
+    /// along with its byte range in the text.
+    pub fn find(&self, original: &str) -> Option<BitapMatch> {
+        let text = self.normalizer.normalize(original);
 
         let text_len = text.chars().count();
         let pattern_len = self.pattern.chars().count();
+
+        // Fast path for exact matches, scored like a window without mismatches plus the boost
+        if let Some(start) = text.find(self.pattern) {
+            let position = text[..start].chars().count();
+            let mut found = self.found(original, position, 0, text_len);
+            found.score += self.exact_match_boost;
+            return Some(found);
+        }
+
         let indices = text.char_indices();
 
-        for i in 0..=text_len.saturating_sub(pattern_len) {
-            let mut mismatches = 0;
-            let mut r = 0;
+        let mut best: Option<(usize, usize)> = None;
+        let mut r = vec![0u64; self.words];
 
+        for i in 0..=text_len.saturating_sub(pattern_len) {
             let end = (i + pattern_len).min(text_len);
 
+            // In texts shorter than the pattern, the characters left uncovered are mismatches
+            let mut mismatches = pattern_len - (end - i);
+            r.fill(0);
+
             let start = indices.clone().nth(i).map(|(idx, _)| idx).unwrap_or(0);
             let end = indices
                 .clone()
@@ -33,19 +125,128 @@ impl<'a> BitapSearcher<'a> {
                 .unwrap_or(text.len());
 
             for (j, character) in text[start..end].chars().enumerate() {
-                r = ((r << 1) | 1) & self.pattern_mask[character as usize];
+                self.step(&mut r, character);
 
-                if r & (1 << j) == 0 {
+                if r[j / u64::BITS as usize] & (1 << (j % u64::BITS as usize)) == 0 {
                     mismatches += 1;
                 }
             }
 
-            if mismatches <= self.max_mismatches {
-                let score = 1.0 - mismatches as f32 / pattern_len as f32;
-                return Some(score);
+            if mismatches <= self.max_mismatches
+                && best.is_none_or(|(_, best_mismatches)| mismatches < best_mismatches)
+            {
+                best = Some((i, mismatches));
+
+                if mismatches == 0 {
+                    break;
+                }
             }
         }
 
-        None
+        let (position, mismatches) = best?;
+
+        Some(self.found(original, position, mismatches, text_len))
+    }
+
+    /// Shifts the state by one character, carrying the top bit of each word into the next one,
+    /// and keeps only the bits of the pattern positions holding that character.
+    fn step(&self, r: &mut [u64], character: char) {
+        let Some(mask) = self.pattern_mask.get(&character) else {
+            // The character is not in the pattern, so no position matches
+            r.fill(0);
+            return;
+        };
+
+        let mut carry = 1;
+
+        for (word, mask) in r.iter_mut().zip(mask) {
+            let next_carry = *word >> (u64::BITS - 1);
+            *word = ((*word << 1) | carry) & mask;
+            carry = next_carry;
+        }
+    }
+
+    /// Builds the match of the window starting at the given character position
+    /// of the normalized text, with its range in the original text.
+    fn found(
+        &self,
+        original: &str,
+        position: usize,
+        mismatches: usize,
+        text_len: usize,
+    ) -> BitapMatch {
+        // The window is cut at the end of the text
+        let end = (position + self.pattern.chars().count()).min(text_len);
+
+        BitapMatch {
+            score: self.score(position, mismatches, text_len),
+            range: Some(self.normalizer.original_range(original, position..end)),
+            mismatches: Some(mismatches),
+        }
+    }
+
+    /// Computes the score of a match with the given mismatches, starting at the given character position.
+    fn score(&self, position: usize, mismatches: usize, text_len: usize) -> f32 {
+        let pattern_len = self.pattern.chars().count();
+        let score = 1.0 - mismatches as f32 / pattern_len as f32;
+
+        score * (1.0 - self.position_weight * position as f32 / text_len.max(1) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Searches the given query in the given text, normalizing the query like the search engine.
+    fn find(query: &str, text: &str) -> Option<BitapMatch> {
+        let config = SearchConfig::default();
+        let pattern = config.normalizer.normalize(query);
+        BitapSearcher::new(&pattern, &config).unwrap().find(text)
+    }
+
+    #[test]
+    fn non_ascii_patterns_match() {
+        let text = "Un café à Москва: привет!";
+
+        let found = find("café", text).unwrap();
+        assert_eq!(found.mismatches, Some(0));
+        assert_eq!(&text[found.range.unwrap()], "café");
+
+        let found = find("ПРИВЕТ", text).unwrap();
+        assert_eq!(found.mismatches, Some(0));
+        assert_eq!(&text[found.range.unwrap()], "привет");
+    }
+
+    #[test]
+    fn non_ascii_patterns_match_with_typos() {
+        let text = "Un café à Москва: привет!";
+
+        let found = find("cafè", text).unwrap();
+        assert_eq!(found.mismatches, Some(1));
+        assert_eq!(&text[found.range.unwrap()], "café");
+
+        let found = find("привит", text).unwrap();
+        assert_ne!(found.mismatches, Some(0));
+        assert_eq!(&text[found.range.unwrap()], "привет");
+    }
+
+    #[test]
+    fn characters_missing_from_the_pattern_never_match() {
+        assert_eq!(find("привет", "日本語のテキスト"), None);
+    }
+
+    #[test]
+    fn empty_texts_never_match() {
+        assert_eq!(find("hello world", ""), None);
+    }
+
+    #[test]
+    fn texts_shorter_than_the_pattern_count_the_missing_characters() {
+        assert_eq!(find("hello world", "hello"), None);
+
+        let found = find("hello world", "hello worl").unwrap();
+        assert_eq!(found.mismatches, Some(1));
+        assert!(found.score < 1.0);
     }
 }
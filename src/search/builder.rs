@@ -0,0 +1,80 @@
+use crate::entities::Entity;
+use crate::search::{MaybeSendSync, SearchConfig, SearchEngine, Searchable};
+use crate::tables::Entry;
+
+/// How far along a [`SearchEngineBuilder`] is, reported after each batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildProgress {
+    /// The number of entries indexed so far.
+    pub indexed: usize,
+    /// The total number of entries expected, if given with [`SearchEngineBuilder::expected`].
+    pub total: Option<usize>,
+}
+
+impl BuildProgress {
+    /// Returns the fraction (between 0 and 1) of the expected entries indexed so far,
+    /// or `None` if the total is unknown.
+    pub fn fraction(&self) -> Option<f32> {
+        match self.total? {
+            0 => Some(1.0),
+            total => Some((self.indexed as f32 / total as f32).min(1.0)),
+        }
+    }
+}
+
+/// Builds a search engine incrementally, one batch of entries at a time.
+///
+/// This allows reporting progress and yielding between batches when indexing large datasets,
+/// instead of blocking until [`SearchEngine::new`] returns.
+pub struct SearchEngineBuilder<'a, T> {
+    engine: SearchEngine<T>,
+    total: Option<usize>,
+    on_progress: Option<Box<dyn FnMut(BuildProgress) + 'a>>,
+}
+
+impl<'a, T: Entity + Searchable + MaybeSendSync> SearchEngineBuilder<'a, T> {
+    /// Creates a builder for an empty search engine with the provided configuration.
+    pub fn new(config: SearchConfig) -> Self {
+        SearchEngineBuilder {
+            engine: SearchEngine::new(Vec::new(), config),
+            total: None,
+            on_progress: None,
+        }
+    }
+
+    /// Sets the total number of entries expected, so progress can be reported as a fraction.
+    pub fn expected(mut self, total: usize) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Sets a callback called with the progress after each batch.
+    pub fn on_progress<F: FnMut(BuildProgress) + 'a>(mut self, on_progress: F) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// Indexes a batch of entries, then reports the progress.
+    pub fn feed(&mut self, batch: Vec<Entry<T>>) {
+        self.engine.add_entries(batch);
+
+        let progress = self.progress();
+
+        if let Some(on_progress) = &mut self.on_progress {
+            on_progress(progress);
+        }
+    }
+
+    /// Returns how many entries were indexed so far.
+    pub fn progress(&self) -> BuildProgress {
+        BuildProgress {
+            indexed: self.engine.len(),
+            total: self.total,
+        }
+    }
+
+    /// Returns the built search engine.
+    pub fn finish(self) -> SearchEngine<T> {
+        self.engine
+    }
+}
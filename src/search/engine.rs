@@ -0,0 +1,556 @@
+use crate::entities::Entity;
+use crate::ids::Id;
+use crate::search::{
+    FieldScore, MaybeSendSync, NgramIndexer, QueryIssue, QueryMode, SCORE_EPSILON, SearchConfig,
+    SearchEngineBuilder, Searchable, TieBreak, words,
+};
+use crate::tables::Entry;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ops::Range;
+
+/// Represents a search result containing an entry and its score.
+#[derive(Debug, Clone)]
+pub struct SearchResult<T> {
+    pub entry: Entry<T>,
+    pub score: f32,
+    /// The scores of the fields that matched, combined into `score`.
+    pub fields: Vec<FieldScore>,
+}
+
+impl<T> SearchResult<T> {
+    pub fn new(entry: Entry<T>, score: f32) -> SearchResult<T> {
+        SearchResult {
+            entry,
+            score,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Returns the label of the field with the best weighted score, the first one on ties,
+    /// to show where the result matched. `None` if no field reported a label.
+    pub fn matched_field(&self) -> Option<&'static str> {
+        self.fields
+            .iter()
+            .reduce(|best, field| {
+                if field.weighted() > best.weighted() {
+                    field
+                } else {
+                    best
+                }
+            })?
+            .label
+    }
+
+    /// Returns the matched byte range of each field reporting one, along with the field label,
+    /// to highlight the matched text.
+    pub fn spans(&self) -> impl Iterator<Item = (Option<&'static str>, Range<usize>)> + '_ {
+        self.fields
+            .iter()
+            .filter_map(|field| Some((field.label, field.range.clone()?)))
+    }
+}
+
+/// Two results are equal when they point to the same entity ID with scores
+/// within [`SCORE_EPSILON`] of each other, so tests do not depend on rounding.
+/// This equality is not transitive, so `SearchResult` is not `Eq`.
+impl<T: Entity> PartialEq for SearchResult<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry.get_id() == other.entry.get_id()
+            && (self.score - other.score).abs() <= SCORE_EPSILON
+    }
+}
+
+/// Results are ordered by score, then by entity ID, scores within [`SCORE_EPSILON`]
+/// of each other counting as equal like with `PartialEq`.
+impl<T: Entity> PartialOrd for SearchResult<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if (self.score - other.score).abs() <= SCORE_EPSILON {
+            return Some(self.entry.get_id().cmp(other.entry.get_id()));
+        }
+
+        self.score.partial_cmp(&other.score)
+    }
+}
+
+/// A search result along with its zero-based position in the sorted results.
+#[derive(Debug, Clone)]
+pub struct RankedResult<T> {
+    pub rank: usize,
+    pub entry: Entry<T>,
+    pub score: f32,
+}
+
+impl<T> RankedResult<T> {
+    /// Attaches the rank to each result, in the order they are given.
+    pub fn rank(results: Vec<SearchResult<T>>) -> Vec<RankedResult<T>> {
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(rank, SearchResult { entry, score, .. })| RankedResult { rank, entry, score })
+            .collect()
+    }
+}
+
+/// The results of a search, along with the reason they are empty if the query was unusable.
+#[derive(Debug, Clone)]
+pub struct SearchOutcome<T> {
+    pub results: Vec<SearchResult<T>>,
+    /// Set when the query could not be used, in which case `results` is empty.
+    pub reason: Option<QueryIssue>,
+}
+
+impl<T> SearchOutcome<T> {
+    /// Returns whether the query could not be used, so the results say nothing about the data.
+    pub fn is_degraded(&self) -> bool {
+        self.reason.is_some()
+    }
+}
+
+/// A scored candidate, ordered from worst to best: by score, then by smallest entity ID,
+/// so that keeping the best ones matches sorting by descending score, then by ascending ID.
+struct Scored<'a, T> {
+    /// The index of the entry in the engine, identifying it across searches.
+    slot: usize,
+    entry: &'a Entry<T>,
+    score: f32,
+    fields: Vec<FieldScore>,
+}
+
+impl<T: Entity> PartialEq for Scored<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Entity> Eq for Scored<'_, T> {}
+
+impl<T: Entity> PartialOrd for Scored<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Entity> Ord for Scored<'_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .partial_cmp(&other.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.entry.get_id().cmp(self.entry.get_id()))
+    }
+}
+
+/// A search engine that allows for fuzzy searching of entries.
+#[derive(Debug, Clone)]
+pub struct SearchEngine<T> {
+    /// The indexed entries, by position in the n-gram index. Removed entries leave a `None`.
+    entries: Vec<Option<Entry<T>>>,
+    /// The number of removed entries still taking a position in `entries`.
+    removed: usize,
+    config: SearchConfig,
+    indexer: NgramIndexer,
+}
+
+impl<T> Default for SearchEngine<T> {
+    fn default() -> Self {
+        SearchEngine {
+            entries: Vec::new(),
+            removed: 0,
+            config: SearchConfig::default(),
+            indexer: NgramIndexer::new(3),
+        }
+    }
+}
+
+impl<T> SearchEngine<T> {
+    /// Removes every entry from the search engine, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.removed = 0;
+        self.indexer.clear();
+    }
+
+    /// Returns the number of entries in the search engine.
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.removed
+    }
+
+    /// Returns whether the search engine has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Entity + Searchable + MaybeSendSync> SearchEngine<T> {
+    /// Creates a new search engine with the provided data and configuration.
+    /// The entries are indexed as they are iterated, without collecting them first.
+    pub fn new<I: IntoIterator<Item = Entry<T>>>(data: I, config: SearchConfig) -> Self {
+        let mut engine = SearchEngine {
+            indexer: NgramIndexer::new(config.ngram_size)
+                .with_normalizer(config.normalizer)
+                .with_stop_words(config.stop_words.clone())
+                .with_tokenization(config.tokenization),
+            config,
+            entries: Vec::new(),
+            removed: 0,
+        };
+
+        engine.add_entries(data);
+        engine
+    }
+
+    /// Creates a builder to index entries in batches, reporting progress along the way.
+    pub fn builder<'a>(config: SearchConfig) -> SearchEngineBuilder<'a, T> {
+        SearchEngineBuilder::new(config)
+    }
+
+    /// Adds new entries to the search engine, indexing them for searching.
+    #[cfg(not(feature = "rayon"))]
+    pub fn add_entries<I: IntoIterator<Item = Entry<T>>>(&mut self, entries: I) {
+        let entries = entries.into_iter();
+        self.entries.reserve(entries.size_hint().0);
+
+        for entry in entries {
+            self.push_entry(entry);
+        }
+    }
+
+    /// Adds new entries to the search engine, indexing them for searching.
+    /// The entries are split in chunks indexed in parallel, then merged in order.
+    #[cfg(feature = "rayon")]
+    pub fn add_entries<I: IntoIterator<Item = Entry<T>>>(&mut self, entries: I) {
+        use rayon::prelude::*;
+
+        let entries = entries.into_iter().collect::<Vec<_>>();
+        let offset = self.entries.len();
+        let chunk_size = entries.len().div_ceil(rayon::current_num_threads()).max(1);
+
+        let indexers = entries
+            .par_chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, entries)| {
+                let mut indexer = self.indexer.empty_like();
+
+                for (pos, entry) in entries.iter().enumerate() {
+                    indexer.set_current_id(offset + chunk * chunk_size + pos);
+                    entry.index(&mut indexer);
+                }
+
+                indexer
+            })
+            .collect::<Vec<_>>();
+
+        for indexer in indexers {
+            self.indexer.merge(indexer);
+        }
+
+        self.entries.extend(entries.into_iter().map(Some));
+    }
+
+    /// Adds a single entry to the search engine, indexing it for searching.
+    pub fn add_entry(&mut self, entry: &Entry<T>) {
+        self.push_entry(entry.clone());
+    }
+
+    /// Indexes the entry under the next position and stores it.
+    fn push_entry(&mut self, entry: Entry<T>) {
+        self.indexer.set_current_id(self.entries.len());
+        entry.index(&mut self.indexer);
+
+        self.entries.push(Some(entry));
+    }
+
+    /// Re-indexes the remaining entries from scratch, dropping the positions of removed entries.
+    fn compact(&mut self) {
+        let entries = self.entries.drain(..).flatten().collect::<Vec<_>>();
+
+        self.clear();
+        self.add_entries(entries);
+    }
+
+    /// Searches for entries matching the given query string.
+    /// Results are sorted by descending score, and results with the same score
+    /// by ascending entity ID, so the order is the same across runs.
+    pub fn search(&self, query: &str) -> Vec<SearchResult<T>> {
+        self.search_top_k(query, usize::MAX)
+    }
+
+    /// Searches for entries matching the given query string, keeping only the `k` best results.
+    /// Results are sorted by descending score, like [`SearchEngine::search`],
+    /// but only the best `k` are kept while scoring instead of sorting every match.
+    pub fn search_top_k(&self, query: &str, k: usize) -> Vec<SearchResult<T>> {
+        self.outcome(query, k, self.config.min_score).results
+    }
+
+    /// Searches for entries matching the given query string, dropping results scoring below
+    /// `min_score` instead of the [`SearchConfig::min_score`] of the engine.
+    pub fn search_with_threshold(&self, query: &str, min_score: f32) -> Vec<SearchResult<T>> {
+        self.outcome(query, usize::MAX, min_score).results
+    }
+
+    /// Searches for entries matching the given query string,
+    /// reporting why the results are empty when the query cannot be used.
+    pub fn search_outcome(&self, query: &str) -> SearchOutcome<T> {
+        self.outcome(query, usize::MAX, self.config.min_score)
+    }
+
+    /// Searches for the `limit` best entries matching the given query string and scoring
+    /// at least `min_score`, reporting why the results are empty when the query cannot be used.
+    fn outcome(&self, query: &str, limit: usize, min_score: f32) -> SearchOutcome<T> {
+        match self.score(query, limit, TieBreak::ById, min_score) {
+            Ok(scores) => SearchOutcome {
+                results: scores
+                    .into_iter()
+                    .map(|scored| SearchResult {
+                        entry: scored.entry.clone(),
+                        score: scored.score,
+                        fields: scored.fields,
+                    })
+                    .collect(),
+                reason: None,
+            },
+            Err(reason) => SearchOutcome {
+                results: Vec::new(),
+                reason: Some(reason),
+            },
+        }
+    }
+
+    /// Scores the entries matching the given query string, keeping the `limit` best ones
+    /// scoring at least `min_score`, sorted by descending score, then by ascending entity ID.
+    /// A bounded heap holds the best entries so far, so only those are sorted in the end.
+    /// With [`TieBreak::IncludeTies`], entries scoring exactly like the worst kept one
+    /// are kept aside instead of being dropped from the heap.
+    fn score(
+        &self,
+        query: &str,
+        limit: usize,
+        tie_break: TieBreak,
+        min_score: f32,
+    ) -> Result<Vec<Scored<'_, T>>, QueryIssue> {
+        let query = self.indexer.normalizer().normalize(query);
+        let searcher = self.config.scorer(&query)?;
+
+        if query.chars().count() < self.config.min_query_len {
+            return Err(QueryIssue::TooShort);
+        }
+
+        // Stop words only narrow down the candidates, as they are left out of the index:
+        // the texts are scored against the query as typed, so phrases still match exactly.
+        // Queries too short to produce any n-gram are scored against every entry
+        let candidates = self
+            .indexer
+            .candidates(
+                &self.indexer.remove_stop_words(&query),
+                self.config.min_ngram_overlap_ratio,
+                self.config.min_ngram_matches,
+            )
+            .unwrap_or_else(|| (0..self.entries.len()).collect());
+
+        let mut heap = BinaryHeap::with_capacity(limit.min(candidates.len()));
+
+        let score_candidate = |id: usize| {
+            let entry = self.entries[id].as_ref()?;
+
+            let mut fields = Vec::new();
+            entry.get_field_scores(&*searcher, &mut fields);
+
+            let score = FieldScore::combine(&fields, self.config.aggregation)
+                .filter(|&score| score >= min_score)?;
+
+            Some(Scored {
+                slot: id,
+                entry,
+                score,
+                fields,
+            })
+        };
+
+        // With the `rayon` feature, candidates are scored in parallel
+        #[cfg(feature = "rayon")]
+        let scored = {
+            use rayon::prelude::*;
+
+            candidates
+                .into_par_iter()
+                .filter_map(score_candidate)
+                .collect::<Vec<_>>()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let scored = candidates.into_iter().filter_map(score_candidate);
+
+        // Entries dropped from the heap while tied with its worst entry, all with the same score
+        let mut ties = Vec::new();
+
+        for scored in scored {
+            // The heap is a min-heap of the best entries, so the worst one is dropped when full
+            heap.push(Reverse(scored));
+
+            if heap.len() <= limit {
+                continue;
+            }
+
+            let Some(Reverse(dropped)) = heap.pop() else {
+                continue;
+            };
+
+            if tie_break == TieBreak::IncludeTies
+                && let Some(Reverse(worst)) = heap.peek()
+            {
+                // The worst score of the heap only grows, so older ties may now fall below it
+                if ties
+                    .first()
+                    .is_some_and(|tie: &Scored<'_, T>| tie.score != worst.score)
+                {
+                    ties.clear();
+                }
+
+                if dropped.score == worst.score {
+                    ties.push(dropped);
+                }
+            }
+        }
+
+        // Ties were dropped as the worst entries, so they sort after the heap, by ascending ID
+        ties.sort_unstable_by(|a, b| b.cmp(a));
+
+        Ok(heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(scored)| scored)
+            .chain(ties)
+            .collect())
+    }
+
+    /// Searches for entries matching the words of the query, split with [`words`],
+    /// each word being scored on its own like a query to [`SearchEngine::search`].
+    /// With [`QueryMode::And`], only entries matching every term are kept,
+    /// and with [`QueryMode::Or`], entries matching any term.
+    /// The score of an entry is the sum of the scores of the terms it matches,
+    /// and results are sorted like the ones of [`SearchEngine::search`].
+    /// Terms that cannot be used, such as terms shorter than `min_query_len`, are ignored.
+    pub fn search_terms(&self, query: &str, mode: QueryMode) -> Vec<SearchResult<T>> {
+        let mut terms = words(query).collect::<Vec<_>>();
+        terms.sort_unstable();
+        terms.dedup();
+
+        let term_results = terms
+            .into_iter()
+            .filter_map(|term| {
+                self.score(term, usize::MAX, TieBreak::ById, self.config.min_score)
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+
+        let mut matched = Vec::<(SearchResult<T>, usize)>::new();
+        let mut positions = HashMap::new();
+
+        for scored in term_results.iter().flatten() {
+            let position = *positions.entry(scored.slot).or_insert_with(|| {
+                matched.push((SearchResult::new(scored.entry.clone(), 0.0), 0));
+                matched.len() - 1
+            });
+
+            let (result, count) = &mut matched[position];
+            result.score += scored.score;
+            result.fields.extend(scored.fields.iter().cloned());
+            *count += 1;
+        }
+
+        let mut results = matched
+            .into_iter()
+            .filter(|&(_, count)| mode == QueryMode::Or || count == term_results.len())
+            .map(|(result, _)| result)
+            .collect::<Vec<_>>();
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.entry.get_id().cmp(b.entry.get_id()))
+        });
+        results
+    }
+
+    /// Searches for entries matching the given query string, attaching the rank of each result.
+    pub fn search_ranked(&self, query: &str) -> Vec<RankedResult<T>> {
+        RankedResult::rank(self.search(query))
+    }
+}
+
+impl<T: Entity + Searchable + MaybeSendSync> SearchEngine<T> {
+    /// Removes the entries with the given IDs from the search engine, returning how many were removed.
+    ///
+    /// Removed entries are skipped by searches right away, while their n-grams are only dropped
+    /// once removed entries outnumber the remaining ones, by re-indexing the remaining entries.
+    pub fn remove_entries<'a, I: IntoIterator<Item = &'a Id<T>>>(&mut self, ids: I) -> usize
+    where
+        T: 'a,
+    {
+        let ids = ids.into_iter().collect::<HashSet<_>>();
+        let mut count = 0;
+
+        if ids.is_empty() {
+            return 0;
+        }
+
+        for slot in &mut self.entries {
+            if slot
+                .as_ref()
+                .is_some_and(|entry| ids.contains(entry.get_id()))
+            {
+                *slot = None;
+                count += 1;
+            }
+        }
+
+        self.removed += count;
+
+        if self.removed > self.len() {
+            self.compact();
+        }
+
+        count
+    }
+
+    /// Removes the entry with the given ID from the search engine, returning whether it was present.
+    pub fn remove_entry(&mut self, id: &Id<T>) -> bool {
+        self.remove_entries([id]) > 0
+    }
+
+    /// Replaces the entry with the same ID as the given one, or adds it if it is absent.
+    pub fn update_entry(&mut self, entry: &Entry<T>) {
+        self.remove_entry(entry.get_id());
+        self.add_entry(entry);
+    }
+
+    /// Searches for entries matching the given query string, keeping the `limit` best results.
+    /// Results are sorted by descending score, then by ascending entity ID,
+    /// and `tie_break` decides what happens to results scoring exactly like the last kept one.
+    /// Like [`SearchEngine::search_top_k`], only the best results are kept while scoring.
+    pub fn search_top(
+        &self,
+        query: &str,
+        limit: usize,
+        tie_break: TieBreak,
+    ) -> Vec<SearchResult<T>> {
+        self.score(query, limit, tie_break, self.config.min_score)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|scored| SearchResult {
+                entry: scored.entry.clone(),
+                score: scored.score,
+                fields: scored.fields,
+            })
+            .collect()
+    }
+
+    /// Searches for entries matching the given query string, returning only their IDs and scores.
+    pub fn search_ids(&self, query: &str) -> Vec<(Id<T>, f32)> {
+        self.score(query, usize::MAX, TieBreak::ById, self.config.min_score)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|scored| (scored.entry.get_id().clone(), scored.score))
+            .collect()
+    }
+}
@@ -0,0 +1,204 @@
+use crate::search::{BitapMatch, Normalizer, QueryIssue, ScoreAggregation, Scorer, SearchConfig};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The weight given to the common prefix by the Winkler adjustment.
+const PREFIX_SCALE: f32 = 0.1;
+
+/// The longest common prefix taken into account by the Winkler adjustment.
+const MAX_PREFIX: usize = 4;
+
+/// Scores texts with the Jaro-Winkler similarity, which handles transpositions
+/// and favors texts sharing a prefix with the query, ranking short texts like names well.
+///
+/// The query is compared to every run of as many consecutive words of the text
+/// as it has, keeping the most similar one, the earliest one on ties.
+/// Its score is lowered by `position_weight` the further it is in the text, like with Bitap.
+/// Texts containing the query exactly score `1.0` before that penalty, plus `exact_match_boost`.
+/// Every text sharing a character with the query matches, so `min_score`
+/// is best raised to drop weak matches.
+#[derive(Debug)]
+pub struct JaroWinklerScorer {
+    /// The characters of the pattern, already normalized.
+    pattern: Vec<char>,
+    /// The number of words in the pattern, the size of the windows compared to it.
+    words: usize,
+    /// How much a match is penalized for starting late in the text, between 0 and 1.
+    position_weight: f32,
+    /// Added to the score of exact matches.
+    exact_match_boost: f32,
+    /// The normalization applied to texts, the same one used for the pattern.
+    normalizer: Normalizer,
+    /// How the scores of several fields or items are combined.
+    aggregation: ScoreAggregation,
+}
+
+impl JaroWinklerScorer {
+    /// Creates a scorer for the given pattern, which must already be normalized
+    /// with the normalizer of `config`.
+    /// Fails if the pattern is empty.
+    pub fn new(pattern: &str, config: &SearchConfig) -> Result<Self, QueryIssue> {
+        if pattern.is_empty() {
+            return Err(QueryIssue::Empty);
+        }
+
+        Ok(JaroWinklerScorer {
+            pattern: pattern.chars().collect(),
+            words: pattern.split_whitespace().count().max(1),
+            position_weight: config.position_weight,
+            exact_match_boost: config.exact_match_boost,
+            normalizer: config.normalizer,
+            aggregation: config.aggregation,
+        })
+    }
+
+    /// Computes the Jaro-Winkler similarity between the pattern and the whole given text,
+    /// normalized like the pattern, instead of its most similar run of words.
+    /// Texts sharing no character with the pattern, like empty ones, score `0.0`.
+    pub fn compare(&self, original: &str) -> f32 {
+        let text = self
+            .normalizer
+            .normalize(original)
+            .chars()
+            .collect::<Vec<_>>();
+
+        self.similarity(&text)
+    }
+
+    /// Computes the Jaro-Winkler similarity between the pattern and the given characters.
+    fn similarity(&self, text: &[char]) -> f32 {
+        let jaro = jaro(&self.pattern, text);
+        let prefix = self
+            .pattern
+            .iter()
+            .zip(text)
+            .take(MAX_PREFIX)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        jaro + prefix as f32 * PREFIX_SCALE * (1.0 - jaro)
+    }
+
+    /// Builds the match of the window spanning the given character positions of the normalized
+    /// text, lowering its score by `position_weight` the further it starts in the text.
+    fn found(
+        &self,
+        original: &str,
+        start: usize,
+        end: usize,
+        score: f32,
+        text_len: usize,
+    ) -> BitapMatch {
+        BitapMatch {
+            score: score * (1.0 - self.position_weight * start as f32 / text_len.max(1) as f32),
+            range: Some(self.normalizer.original_range(original, start..end)),
+            mismatches: None,
+        }
+    }
+}
+
+impl Scorer for JaroWinklerScorer {
+    fn find(&self, original: &str) -> Option<BitapMatch> {
+        let text = self
+            .normalizer
+            .normalize(original)
+            .chars()
+            .collect::<Vec<_>>();
+
+        // Fast path for exact matches, which may start or end inside a word
+        if let Some(start) = text
+            .windows(self.pattern.len())
+            .position(|window| window == self.pattern)
+        {
+            let end = start + self.pattern.len();
+            let mut found = self.found(original, start, end, 1.0, text.len());
+            found.score += self.exact_match_boost;
+            return Some(found);
+        }
+
+        // The character range of each word of the text
+        let mut words = Vec::new();
+        let mut start = None;
+
+        for (i, ch) in text.iter().enumerate() {
+            match (ch.is_whitespace(), start) {
+                (false, None) => start = Some(i),
+                (true, Some(word_start)) => {
+                    words.push(word_start..i);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(word_start) = start {
+            words.push(word_start..text.len());
+        }
+
+        let size = self.words.min(words.len());
+        let mut best: Option<(f32, usize, usize)> = None;
+
+        for window in words.windows(size.max(1)) {
+            let (start, end) = (window[0].start, window[window.len() - 1].end);
+            let similarity = self.similarity(&text[start..end]);
+
+            if best.is_none_or(|(best_similarity, _, _)| similarity > best_similarity) {
+                best = Some((similarity, start, end));
+            }
+        }
+
+        let (similarity, start, end) = best.filter(|&(similarity, _, _)| similarity > 0.0)?;
+
+        Some(self.found(original, start, end, similarity, text.len()))
+    }
+
+    fn aggregation(&self) -> ScoreAggregation {
+        self.aggregation
+    }
+}
+
+/// Computes the Jaro similarity between two sequences of characters, between 0 and 1.
+fn jaro(a: &[char], b: &[char]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let distance = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut a_matched = vec![false; a.len()];
+    let mut b_matched = vec![false; b.len()];
+    let mut matches = 0;
+
+    for (i, ch) in a.iter().enumerate() {
+        let start = i.saturating_sub(distance);
+        let end = (i + distance + 1).min(b.len());
+
+        for j in start..end {
+            if !b_matched[j] && b[j] == *ch {
+                a_matched[i] = true;
+                b_matched[j] = true;
+                matches += 1;
+                break;
+            }
+        }
+    }
+
+    if matches == 0 {
+        return 0.0;
+    }
+
+    // Matched characters appearing in a different order, counted in pairs
+    let a_chars = a.iter().zip(&a_matched).filter(|&(_, &matched)| matched);
+    let b_chars = b.iter().zip(&b_matched).filter(|&(_, &matched)| matched);
+    let transpositions = a_chars
+        .zip(b_chars)
+        .filter(|((a, _), (b, _))| a != b)
+        .count()
+        / 2;
+
+    let matches = matches as f32;
+
+    (matches / a.len() as f32
+        + matches / b.len() as f32
+        + (matches - transpositions as f32) / matches)
+        / 3.0
+}
@@ -1,18 +1,87 @@
-use crate::tables::Entry;
-pub use bitap::BitapSearcher;
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+
+pub use bitap::{BitapMatch, BitapSearcher};
+#[cfg(feature = "std")]
+pub use builder::{BuildProgress, SearchEngineBuilder};
+#[cfg(feature = "std")]
+pub use engine::{RankedResult, SearchEngine, SearchOutcome, SearchResult};
+pub use jaro_winkler::JaroWinklerScorer;
 pub use ngram::NgramIndexer;
-pub use searchable::Searchable;
-use std::collections::HashMap;
+pub use normalizer::Normalizer;
+pub use scorer::Scorer;
+pub use searchable::{FieldScore, Searchable};
+pub use tokenizer::{tokenize, words};
 
 pub mod bitap;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+mod engine;
+pub mod jaro_winkler;
 pub mod ngram;
+pub mod normalizer;
+pub mod scorer;
 pub mod searchable;
+#[cfg(feature = "stop-words")]
+pub mod stop_words;
+pub mod tokenizer;
+
+/// The bounds searched entities must meet: `Send + Sync` with the `rayon` feature,
+/// so entries can be indexed and scored across threads, and none otherwise.
+#[cfg(feature = "rayon")]
+pub trait MaybeSendSync: Send + Sync {}
+
+#[cfg(feature = "rayon")]
+impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+
+/// The bounds searched entities must meet: `Send + Sync` with the `rayon` feature,
+/// so entries can be indexed and scored across threads, and none otherwise.
+#[cfg(not(feature = "rayon"))]
+pub trait MaybeSendSync {}
+
+#[cfg(not(feature = "rayon"))]
+impl<T: ?Sized> MaybeSendSync for T {}
 
 /// Configuration for the search engine.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SearchConfig {
     pub ngram_size: usize,
     pub max_distance: usize,
+    /// The fraction (between 0 and 1) of the query's n-grams a candidate must share to be scored.
+    /// With the default of `0.0`, any candidate sharing a single n-gram is scored.
+    pub min_ngram_overlap_ratio: f32,
+    /// The number of the query's n-grams a candidate must share to be scored,
+    /// or all of them for queries with fewer n-grams.
+    /// With the default of `0`, only `min_ngram_overlap_ratio` applies.
+    pub min_ngram_matches: usize,
+    /// The number of characters a query needs to be searched, shorter queries are rejected
+    /// with [`QueryIssue::TooShort`]. Queries too short to produce any n-gram are still searched,
+    /// by scoring every entry instead of the candidates sharing n-grams with them.
+    pub min_query_len: usize,
+    /// How much a match is penalized for starting late in the text, between 0 and 1.
+    /// A match at the very end of a text loses up to this fraction of its score.
+    pub position_weight: f32,
+    /// Added to the score of texts containing the query exactly, so they can outrank fuzzy matches.
+    /// Exact matches already score `1.0` at the start of a text; with a boost of at least
+    /// `position_weight`, they always outrank fuzzy matches. No boost is added by default.
+    pub exact_match_boost: f32,
+    /// The lowest score a result can have, lower scoring results are dropped.
+    /// With the default of `0.0`, every match is kept.
+    pub min_score: f32,
+    /// How the scores of the fields of an item, or of the items of a collection, are combined.
+    pub aggregation: ScoreAggregation,
+    /// The normalization applied to both indexed texts and queries.
+    pub normalizer: Normalizer,
+    /// How texts and queries are split before generating their n-grams.
+    pub tokenization: Tokenization,
+    /// Words left out of the n-gram index, given in lowercase. They are left out of queries
+    /// when looking up candidates too, but candidates are scored against the whole query.
+    /// Empty by default; with the `stop-words` feature, `stop_words::english()` is a ready-made set.
+    pub stop_words: BTreeSet<String>,
+    /// The algorithm scoring the texts of candidates against the query.
+    pub scoring: Scoring,
 }
 
 impl Default for SearchConfig {
@@ -20,127 +89,117 @@ impl Default for SearchConfig {
         SearchConfig {
             ngram_size: 3,
             max_distance: 2,
+            min_ngram_overlap_ratio: 0.0,
+            min_ngram_matches: 0,
+            min_query_len: 1,
+            position_weight: 0.1,
+            exact_match_boost: 0.0,
+            min_score: 0.0,
+            aggregation: ScoreAggregation::default(),
+            normalizer: Normalizer::default(),
+            tokenization: Tokenization::default(),
+            stop_words: BTreeSet::new(),
+            scoring: Scoring::default(),
         }
     }
 }
 
-/// Represents a search result containing an entry and its score.
-#[derive(Debug, Clone, PartialOrd)]
-pub struct SearchResult<T> {
-    pub entry: Entry<T>,
-    pub score: f32,
-}
-
-impl<T> SearchResult<T> {
-    pub fn new(entry: Entry<T>, score: f32) -> SearchResult<T> {
-        SearchResult { entry, score }
+impl SearchConfig {
+    /// Creates the scorer picked by `scoring` for the given pattern,
+    /// which must already be normalized with `normalizer`.
+    /// Fails if the pattern is empty.
+    pub fn scorer<'a>(&self, pattern: &'a str) -> Result<Box<dyn Scorer + 'a>, QueryIssue> {
+        Ok(match self.scoring {
+            Scoring::Bitap => Box::new(BitapSearcher::new(pattern, self)?),
+            Scoring::JaroWinkler => Box::new(JaroWinklerScorer::new(pattern, self)?),
+        })
     }
 }
 
-impl<T> PartialEq for SearchResult<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.entry == other.entry && self.score == other.score
-    }
+/// The tolerance used when comparing search scores for equality.
+pub const SCORE_EPSILON: f32 = 1e-5;
+
+/// How results sharing the score of the last kept result are handled when capping results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// Keeps exactly `limit` results, picking among tied results by ascending entity ID.
+    #[default]
+    ById,
+    /// Keeps every result scoring exactly like the last kept one, which may exceed `limit`.
+    IncludeTies,
 }
 
-impl<T> Eq for SearchResult<T> {}
-
-/// A search engine that allows for fuzzy searching of entries.
-#[derive(Debug, Clone)]
-pub struct SearchEngine<T> {
-    entries: Vec<Entry<T>>,
-    max_bitap_mismatches: usize,
-    indexer: NgramIndexer,
+/// How several scores are combined into one, for the fields of an item
+/// or the items of a collection. Only the scores that matched are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreAggregation {
+    /// Keeps the best score, so matching several fields ranks no higher than matching one.
+    #[default]
+    Max,
+    /// Adds up the scores, so items matching in several places rank higher.
+    Sum,
+    /// Averages the scores.
+    Mean,
 }
 
-impl<T> Default for SearchEngine<T> {
-    fn default() -> Self {
-        SearchEngine {
-            entries: Vec::new(),
-            max_bitap_mismatches: 2,
-            indexer: NgramIndexer::new(3),
-        }
+impl ScoreAggregation {
+    /// Combines the given scores, or returns `None` if there are none.
+    pub fn aggregate(self, scores: impl IntoIterator<Item = f32>) -> Option<f32> {
+        let mut scores = scores.into_iter();
+        let first = scores.next()?;
+
+        Some(match self {
+            ScoreAggregation::Max => scores.fold(first, f32::max),
+            ScoreAggregation::Sum => scores.fold(first, |sum, score| sum + score),
+            ScoreAggregation::Mean => {
+                let (sum, count) =
+                    scores.fold((first, 1), |(sum, count), score| (sum + score, count + 1));
+                sum / count as f32
+            }
+        })
     }
 }
 
-impl<T: Searchable> SearchEngine<T> {
-    /// Creates a new search engine with the provided data and configuration.
-    pub fn new(data: Vec<Entry<T>>, config: SearchConfig) -> Self {
-        let mut engine = SearchEngine {
-            indexer: NgramIndexer::new(config.ngram_size),
-            max_bitap_mismatches: config.max_distance,
-            entries: data,
-        };
-
-        for (id, entry) in engine.entries.iter().enumerate() {
-            engine.indexer.set_current_id(id);
-
-            entry.index(&mut engine.indexer);
-        }
-
-        engine
-    }
-
-    /// Adds new entries to the search engine, indexing them for searching.
-    pub fn add_entries(&mut self, entries: Vec<Entry<T>>) {
-        let mut id = self.entries.len();
-
-        for entry in &entries {
-            self.indexer.set_current_id(id);
-            entry.index(&mut self.indexer);
-            id += 1;
-        }
-
-        self.entries.extend(entries);
-    }
-
-    /// Searches for entries matching the given query string.
-    pub fn search(&self, query: &str) -> Vec<SearchResult<T>> {
-        // Supports only queries between 1 and 32 characters.
-        if query.is_empty() || query.len() > u32::BITS as usize {
-            return Vec::new();
-        }
-
-        let query = query.to_lowercase();
-
-        let mut pattern_mask = [0u32; 1024];
-
-        for (i, ch) in query.chars().enumerate() {
-            pattern_mask[ch as usize] |= 1 << i;
-        }
+/// How texts are split before generating their n-grams, see [`SearchConfig::tokenization`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tokenization {
+    /// N-grams span the whole text, across words, so a query can match anywhere in the text.
+    #[default]
+    Whole,
+    /// N-grams are generated for each word on its own, splitting on non-alphanumeric characters.
+    /// Words are padded with a space on both sides, so n-grams at the start and end of a word
+    /// are kept apart from the ones inside it, while a query still matches inside longer words.
+    Words,
+}
 
-        let ngrams = self.indexer.generate_ngrams(&query);
-        let mut candidates = HashMap::new();
+/// The algorithm scoring texts against a query, see [`SearchConfig::scoring`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scoring {
+    /// Bitap with up to `max_distance` mismatches, scored by the fraction of matching characters.
+    #[default]
+    Bitap,
+    /// The Jaro-Winkler similarity, handling transpositions and favoring shared prefixes,
+    /// which ranks short texts like names better. See [`JaroWinklerScorer`].
+    JaroWinkler,
+}
 
-        for ngram in ngrams {
-            if let Some(docs) = self.indexer.get(&ngram) {
-                for &id in docs {
-                    *candidates.entry(id).or_insert(0) += 1;
-                }
-            }
-        }
+/// How the terms of a query are combined by `SearchEngine::search_terms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryMode {
+    /// Keeps the entries matching every term.
+    #[default]
+    And,
+    /// Keeps the entries matching any term.
+    Or,
+}
 
-        let searcher = BitapSearcher {
-            pattern: &query,
-            pattern_mask,
-            max_mismatches: self.max_bitap_mismatches,
-        };
-
-        let mut results = candidates
-            .into_iter()
-            .filter_map(|(id, _)| {
-                let entry = &self.entries[id];
-                let score = entry.get_score(&searcher)?;
-                Some(SearchResult::new(entry.clone(), score))
-            })
-            .collect::<Vec<_>>();
-
-        results.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-
-        results
-    }
+/// Why a query could not be used to search, leading to empty results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryIssue {
+    /// The query is empty.
+    Empty,
+    /// The query has fewer characters than [`SearchConfig::min_query_len`].
+    TooShort,
+    /// The search engine could not be used, as another thread panicked while updating it.
+    Unavailable,
 }
@@ -1,3 +1,12 @@
+use crate::search::{Normalizer, Tokenization, words};
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
 
 /// Used to create an index to easily narrow down search results
@@ -5,57 +14,245 @@ use std::collections::HashMap;
 pub struct NgramIndexer {
     /// The size of the n-grams to generate
     ngram_size: usize,
+    /// The size used instead of `ngram_size` while indexing a field, see `with_ngram_size`
+    field_ngram_size: Option<usize>,
+    /// Every n-gram size indexed so far, all consulted when looking up a query
+    sizes: BTreeSet<usize>,
     /// The index mapping n-grams to entry IDs
     index: HashMap<String, Vec<usize>>,
     /// The current ID to assign to the next indexed entry
     current_id: usize,
+    /// The normalization applied to indexed texts, shared with queries
+    normalizer: Normalizer,
+    /// The words left out of indexed texts and of queries looking up candidates, see `with_stop_words`
+    stop_words: BTreeSet<String>,
+    /// How texts are split before generating their n-grams
+    tokenization: Tokenization,
 }
 
 impl NgramIndexer {
     pub fn new(ngram_size: usize) -> Self {
         NgramIndexer {
             ngram_size,
+            field_ngram_size: None,
+            sizes: BTreeSet::from([ngram_size]),
             index: HashMap::new(),
             current_id: 0,
+            normalizer: Normalizer::default(),
+            stop_words: BTreeSet::new(),
+            tokenization: Tokenization::default(),
         }
     }
 
+    /// Sets the normalization applied to indexed texts.
+    pub fn with_normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// Sets how texts are split before generating their n-grams, for indexed texts and queries.
+    pub fn with_tokenization(mut self, tokenization: Tokenization) -> Self {
+        self.tokenization = tokenization;
+        self
+    }
+
+    /// Sets the words left out of indexed texts, given in lowercase.
+    /// Queries looking up candidates should be stripped of them too, with `remove_stop_words`.
+    pub fn with_stop_words(mut self, stop_words: BTreeSet<String>) -> Self {
+        self.stop_words = stop_words;
+        self
+    }
+
+    /// Removes the stop words from the normalized text, joining the remaining words with spaces.
+    /// Words are split like [`tokenize`](crate::search::tokenize), so punctuation is dropped.
+    /// The text is borrowed when there are no stop words to look for.
+    pub fn remove_stop_words<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.stop_words.is_empty() {
+            return Cow::Borrowed(text);
+        }
+
+        let words = words(text)
+            .filter(|word| !self.stop_words.contains(word.to_lowercase().as_str()))
+            .collect::<Vec<_>>();
+
+        Cow::Owned(words.join(" "))
+    }
+
+    /// Returns the normalization applied to indexed texts, to apply to queries as well.
+    pub fn normalizer(&self) -> &Normalizer {
+        &self.normalizer
+    }
+
     /// Indexes the input string by generating n-grams and storing them in the index.
     pub fn index(&mut self, input: &str) {
-        let input = input.to_lowercase();
-        let ngrams = self.generate_ngrams(&input);
+        let size = self.field_ngram_size.unwrap_or(self.ngram_size);
+        let input = self.normalizer.normalize(input);
+        let input = self.remove_stop_words(&input);
+        let ngrams = self.tokenize_ngrams(&input, size);
+
+        self.sizes.insert(size);
 
         for ngram in ngrams {
             self.index.entry(ngram).or_default().push(self.current_id);
         }
     }
 
+    /// Runs `f` with texts indexed using n-grams of the given size instead of the default one.
+    /// Used by `#[search(ngram = ...)]` to tune the n-gram size of a single field.
+    pub fn with_ngram_size<F: FnOnce(&mut Self)>(&mut self, size: usize, f: F) {
+        let previous = self.field_ngram_size.replace(size);
+        f(self);
+        self.field_ngram_size = previous;
+    }
+
+    /// Indexes the input string under the given ID, so the indexer can be used on its own
+    /// as an inverted index over entries identified by the caller.
+    pub fn index_with_id(&mut self, id: usize, input: &str) {
+        self.set_current_id(id);
+        self.index(input);
+    }
+
+    /// Removes every indexed n-gram, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.sizes.retain(|&size| size == self.ngram_size);
+        self.current_id = 0;
+    }
+
+    /// Creates an empty indexer with the same n-gram size and normalization,
+    /// to index entries on another thread before merging it back.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn empty_like(&self) -> Self {
+        NgramIndexer::new(self.ngram_size)
+            .with_normalizer(self.normalizer)
+            .with_stop_words(self.stop_words.clone())
+            .with_tokenization(self.tokenization)
+    }
+
+    /// Moves the n-grams indexed by `other` into this indexer.
+    /// The IDs indexed by `other` must come after the ones of this indexer,
+    /// so the IDs of each n-gram stay in ascending order.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn merge(&mut self, other: NgramIndexer) {
+        self.sizes.extend(other.sizes);
+
+        for (ngram, ids) in other.index {
+            self.index.entry(ngram).or_default().extend(ids);
+        }
+    }
+
     /// Sets the current ID for the next indexed entry.
     pub(crate) fn set_current_id(&mut self, id: usize) {
         self.current_id = id;
     }
 
     /// Retrieves the IDs associated with a given n-gram.
-    pub(crate) fn get(&self, ngram: &str) -> Option<&Vec<usize>> {
-        self.index.get(ngram)
+    /// The n-gram is looked up as is, so it should already be normalized.
+    pub fn get(&self, ngram: &str) -> Option<&[usize]> {
+        self.index.get(ngram).map(Vec::as_slice)
+    }
+
+    /// Returns the IDs of the entries sharing enough n-grams with the query, in ascending order.
+    /// For each n-gram size indexed, an entry must share at least `min_overlap_ratio`
+    /// of the query's n-grams of that size, and at least `min_matches` of them, or all of them
+    /// for queries with fewer n-grams; passing for a single size is enough.
+    /// Returns `None` if the query is too short to produce any n-gram.
+    /// The query is used as is, so it should already be normalized.
+    pub fn candidates(
+        &self,
+        query: &str,
+        min_overlap_ratio: f32,
+        min_matches: usize,
+    ) -> Option<Vec<usize>> {
+        let mut candidates = BTreeSet::new();
+        let mut has_ngrams = false;
+
+        for &size in &self.sizes {
+            let ngrams = self.tokenize_ngrams(query, size);
+
+            if ngrams.is_empty() {
+                continue;
+            }
+
+            has_ngrams = true;
+
+            // Rounded up by hand, as `f32::ceil` needs `std`
+            let exact_overlap = ngrams.len() as f32 * min_overlap_ratio;
+            let min_overlap = exact_overlap as usize;
+            let min_overlap = min_overlap + usize::from((min_overlap as f32) < exact_overlap);
+            let min_overlap = min_overlap.max(min_matches.min(ngrams.len()));
+            let mut counts = HashMap::new();
+
+            for ngram in ngrams {
+                for &id in self.get(&ngram).unwrap_or_default() {
+                    *counts.entry(id).or_insert(0) += 1;
+                }
+            }
+
+            candidates.extend(
+                counts
+                    .into_iter()
+                    .filter(|&(_, count)| count >= min_overlap)
+                    .map(|(id, _)| id),
+            );
+        }
+
+        has_ngrams.then(|| candidates.into_iter().collect())
     }
 
-    /// Generates n-grams from the input string.
-    pub(crate) fn generate_ngrams(&self, input: &str) -> Vec<String> {
+    /// Generates n-grams of the given size from the input string, following the tokenization.
+    fn tokenize_ngrams(&self, input: &str, ngram_size: usize) -> Vec<String> {
+        match self.tokenization {
+            Tokenization::Whole => Self::generate_ngrams(input, ngram_size),
+            Tokenization::Words => words(input)
+                .flat_map(|word| Self::generate_ngrams(&format!(" {word} "), ngram_size))
+                .collect(),
+        }
+    }
+
+    /// Generates n-grams of the given size from the input string.
+    fn generate_ngrams(input: &str, ngram_size: usize) -> Vec<String> {
         let len = input.chars().count();
 
-        if self.ngram_size == 0 || len < self.ngram_size {
+        if ngram_size == 0 || len < ngram_size {
+            return Vec::new();
+        }
+
+        // The byte offset of each character, followed by the end of the input
+        let boundaries = input
+            .char_indices()
+            .map(|(idx, _)| idx)
+            .chain([input.len()])
+            .collect::<Vec<_>>();
+
+        boundaries
+            .windows(ngram_size + 1)
+            .map(|window| input[window[0]..window[ngram_size]].to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// The previous quadratic implementation, kept as a reference for `generate_ngrams`.
+    fn reference_ngrams(input: &str, ngram_size: usize) -> Vec<String> {
+        let len = input.chars().count();
+
+        if ngram_size == 0 || len < ngram_size {
             return Vec::new();
         }
 
         let mut ngrams = Vec::new();
         let indices = input.char_indices();
 
-        for i in 0..=len - self.ngram_size {
+        for i in 0..=len - ngram_size {
             let start = indices.clone().nth(i).map(|(idx, _)| idx).unwrap_or(0);
             let end = indices
                 .clone()
-                .nth(i + self.ngram_size)
+                .nth(i + ngram_size)
                 .map(|(idx, _)| idx)
                 .unwrap_or(input.len());
 
@@ -64,4 +261,23 @@ impl NgramIndexer {
 
         ngrams
     }
+
+    #[test]
+    fn long_inputs_match_the_reference() {
+        // Mixes one to four byte characters over 10,000 characters
+        let input = "aé€😀 ".repeat(2_000);
+        assert_eq!(input.chars().count(), 10_000);
+
+        assert_eq!(
+            NgramIndexer::generate_ngrams(&input, 3),
+            reference_ngrams(&input, 3)
+        );
+    }
+
+    #[test]
+    fn short_inputs_have_no_ngrams() {
+        assert!(NgramIndexer::generate_ngrams("ab", 3).is_empty());
+        assert!(NgramIndexer::generate_ngrams("abc", 0).is_empty());
+        assert_eq!(NgramIndexer::generate_ngrams("añb", 3), vec!["añb"]);
+    }
 }
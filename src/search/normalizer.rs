@@ -0,0 +1,119 @@
+use alloc::borrow::Cow;
+use core::ops::Range;
+
+/// The normalization applied to texts before they are indexed or searched.
+/// The same normalizer is used for indexed texts and queries, so both always match.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Normalizer {
+    /// Whether to keep the case of texts, instead of lowercasing them.
+    pub case_sensitive: bool,
+}
+
+impl Normalizer {
+    /// Normalizes the text, borrowing it when no change is needed.
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.case_sensitive {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(text.to_lowercase())
+        }
+    }
+
+    /// Maps a range of characters of the normalized text back to the byte range of `original`
+    /// they were normalized from, to highlight matches found in the normalized text.
+    /// Lowercasing can turn a character into several, like `'İ'` into `"i̇"`, so a range
+    /// starting or ending among the characters of such an expansion covers the whole character.
+    pub fn original_range(&self, original: &str, chars: Range<usize>) -> Range<usize> {
+        let mut position = 0;
+        let mut start = None;
+        let mut end = original.len();
+
+        for (idx, ch) in original.char_indices() {
+            let next = position
+                + if self.case_sensitive {
+                    1
+                } else {
+                    ch.to_lowercase().count()
+                };
+
+            if start.is_none() && chars.start < next {
+                start = Some(idx);
+            }
+
+            if chars.end <= next {
+                end = if chars.end > position {
+                    idx + ch.len_utf8()
+                } else {
+                    idx
+                };
+                break;
+            }
+
+            position = next;
+        }
+
+        let start = start.unwrap_or(original.len());
+        start..end.max(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::NgramIndexer;
+    use alloc::vec;
+
+    /// Looks up a query the way the search engine does, normalizing it with the indexer's normalizer.
+    fn lookup(indexer: &NgramIndexer, query: &str) -> Option<alloc::vec::Vec<usize>> {
+        let query = indexer.normalizer().normalize(query);
+        indexer.candidates(&query, 1.0, 0)
+    }
+
+    #[test]
+    fn index_and_query_normalization_match() {
+        let mut indexer = NgramIndexer::new(3);
+        indexer.index_with_id(0, "Crème BRÛLÉE");
+
+        assert_eq!(lookup(&indexer, "crème brûlée"), Some(vec![0]));
+        assert_eq!(lookup(&indexer, "CRÈME BRÛLÉE"), Some(vec![0]));
+    }
+
+    #[test]
+    fn case_sensitive_normalization_matches_on_both_sides() {
+        let normalizer = Normalizer {
+            case_sensitive: true,
+        };
+        let mut indexer = NgramIndexer::new(3).with_normalizer(normalizer);
+        indexer.index_with_id(0, "Crème");
+
+        assert_eq!(lookup(&indexer, "Crème"), Some(vec![0]));
+        assert_eq!(lookup(&indexer, "crème"), Some(vec![]));
+    }
+
+    #[test]
+    fn original_ranges_follow_expanded_characters() {
+        let normalizer = Normalizer::default();
+        let original = "İstanbul Café";
+
+        // 'İ' is lowercased to two characters, shifting the rest of the text by one
+        assert_eq!(normalizer.normalize(original).chars().count(), 14);
+        assert_eq!(
+            &original[normalizer.original_range(original, 2..10)],
+            "stanbul "
+        );
+        assert_eq!(
+            &original[normalizer.original_range(original, 10..14)],
+            "Café"
+        );
+        assert_eq!(&original[normalizer.original_range(original, 1..3)], "İs");
+        assert_eq!(normalizer.original_range(original, 20..25), 15..15);
+    }
+
+    #[test]
+    fn normalizing_twice_changes_nothing() {
+        let normalizer = Normalizer::default();
+        let once = normalizer.normalize("Crème BRÛLÉE");
+
+        assert_eq!(normalizer.normalize(&once), once);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::search::{BitapMatch, BitapSearcher, MaybeSendSync, ScoreAggregation};
+
+/// Scores texts against a search query, passed to [`Searchable`](crate::search::Searchable)
+/// implementations so they can score their texts whichever algorithm is used.
+/// [`SearchConfig::scoring`](crate::search::SearchConfig::scoring) picks the scorer
+/// used by the search engine.
+pub trait Scorer: MaybeSendSync {
+    /// Finds the best match of the query in the given text, along with its byte range in the text.
+    /// Returns `None` if the text does not match.
+    fn find(&self, text: &str) -> Option<BitapMatch>;
+
+    /// Calculates a score for the given text, between 0 and 1.
+    /// Returns `None` if the text does not match.
+    fn get_score(&self, text: &str) -> Option<f32> {
+        self.find(text).map(|found| found.score)
+    }
+
+    /// Returns how the scores of several fields or items are combined.
+    fn aggregation(&self) -> ScoreAggregation;
+}
+
+impl Scorer for BitapSearcher<'_> {
+    fn find(&self, text: &str) -> Option<BitapMatch> {
+        BitapSearcher::find(self, text)
+    }
+
+    fn get_score(&self, text: &str) -> Option<f32> {
+        BitapSearcher::get_score(self, text)
+    }
+
+    fn aggregation(&self) -> ScoreAggregation {
+        BitapSearcher::aggregation(self)
+    }
+}
@@ -1,16 +1,111 @@
-use crate::search::{BitapSearcher, NgramIndexer};
-use std::rc::Rc;
-use std::sync::Arc;
+use crate::search::{BitapMatch, NgramIndexer, ScoreAggregation, Scorer};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::String;
+#[cfg(any(feature = "chrono", feature = "time"))]
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
 /// Trait for elements that can be indexed and searched.
 /// Implemented by default on all [`Entity`] types created with the `#[entity]` macro.
 ///
 /// [`Entity`]: crate::entities::Entity
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not `Searchable`",
+    label = "this field type cannot be searched",
+    note = "only mark fields whose type implements `Searchable` with `#[search]`"
+)]
 pub trait Searchable {
     /// Indexes the item using the provided `NgramIndexer`.
     fn index(&self, indexer: &mut NgramIndexer);
-    /// Retrieves the score for the item based on a search query using the provided `BitapSearcher`.
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32>;
+    /// Retrieves the score for the item based on a search query using the provided `Scorer`.
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32>;
+    /// Finds where the item matched a search query, for highlighting.
+    /// By default, only the score is known and the range and mismatches are left empty.
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        self.get_score(searcher).map(|score| BitapMatch {
+            score,
+            range: None,
+            mismatches: None,
+        })
+    }
+    /// Scores each searchable field of the item, pushing the ones that matched to `scores`.
+    /// By default, the item is reported as a single unlabeled field with a weight of `1.0`.
+    fn get_field_scores(&self, searcher: &dyn Scorer, scores: &mut Vec<FieldScore>) {
+        if let Some(found) = self.get_match(searcher) {
+            scores.push(FieldScore {
+                label: None,
+                weight: 1.0,
+                score: found.score,
+                range: found.range,
+            });
+        }
+    }
+    /// Appends the searchable text of the item to `buffer`, separated by a space from any previous text.
+    /// Used to search over several fields at once with `#[search(concat)]`; does nothing by default.
+    fn write_text(&self, _buffer: &mut String) {}
+}
+
+/// The score of a single searchable field of an item, along with where it matched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldScore {
+    /// The label of the field, set with `#[search(label = "...")]` or defaulting to the field name.
+    pub label: Option<&'static str>,
+    /// The weight of the field, set with `#[search(weight = ...)]` or defaulting to `1.0`.
+    pub weight: f32,
+    /// The unweighted score of the field.
+    pub score: f32,
+    /// The byte range of the match in the text of the field, for highlights and snippets.
+    /// For collections, the range is within the best matching item.
+    /// `None` when the field type does not report where it matched (see [`Searchable::get_match`]).
+    pub range: Option<Range<usize>>,
+}
+
+impl FieldScore {
+    /// Returns the weighted score of the field.
+    pub fn weighted(&self) -> f32 {
+        self.weight * self.score
+    }
+
+    /// Combines field scores into the score of the item by aggregating the weighted scores,
+    /// `max(weight * score)` by default, or `None` if no field matched.
+    pub fn combine(scores: &[FieldScore], aggregation: ScoreAggregation) -> Option<f32> {
+        aggregation.aggregate(scores.iter().map(FieldScore::weighted))
+    }
+}
+
+/// Returns the best of the given matches, the first one on ties,
+/// with the scores of every match combined as its score.
+fn best_match(
+    matches: impl Iterator<Item = BitapMatch>,
+    aggregation: ScoreAggregation,
+) -> Option<BitapMatch> {
+    let matches = matches.collect::<Vec<_>>();
+    let score = aggregation.aggregate(matches.iter().map(|found| found.score))?;
+
+    let best = matches.into_iter().reduce(|best, found| {
+        if found.score > best.score {
+            found
+        } else {
+            best
+        }
+    })?;
+
+    Some(BitapMatch { score, ..best })
+}
+
+/// Appends `text` to `buffer`, separated by a space if `buffer` is not empty.
+pub(crate) fn push_text(buffer: &mut String, text: &str) {
+    if !buffer.is_empty() {
+        buffer.push(' ');
+    }
+
+    buffer.push_str(text);
 }
 
 impl Searchable for String {
@@ -18,9 +113,17 @@ impl Searchable for String {
         indexer.index(self);
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
         searcher.get_score(self)
     }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        searcher.find(self)
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, self);
+    }
 }
 
 impl<T: Searchable> Searchable for Vec<T> {
@@ -30,16 +133,81 @@ impl<T: Searchable> Searchable for Vec<T> {
         }
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
-        let list = self
-            .iter()
-            .filter_map(|item| item.get_score(searcher))
-            .collect::<Vec<_>>();
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher
+            .aggregation()
+            .aggregate(self.iter().filter_map(|item| item.get_score(searcher)))
+    }
 
-        if list.is_empty() {
-            None
-        } else {
-            Some(list.into_iter().fold(0f32, f32::max))
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        best_match(
+            self.iter().filter_map(|item| item.get_match(searcher)),
+            searcher.aggregation(),
+        )
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        for item in self {
+            item.write_text(buffer);
+        }
+    }
+}
+
+/// Only the values of the map are indexed, not its keys.
+/// The values are visited in the iteration order of the map, which is arbitrary.
+#[cfg(feature = "std")]
+impl<K, V: Searchable, S> Searchable for HashMap<K, V, S> {
+    fn index(&self, indexer: &mut NgramIndexer) {
+        for value in self.values() {
+            value.index(indexer);
+        }
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher
+            .aggregation()
+            .aggregate(self.values().filter_map(|value| value.get_score(searcher)))
+    }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        best_match(
+            self.values().filter_map(|value| value.get_match(searcher)),
+            searcher.aggregation(),
+        )
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        for value in self.values() {
+            value.write_text(buffer);
+        }
+    }
+}
+
+/// Only the values of the map are indexed, not its keys.
+/// The values are visited in the order of their keys.
+impl<K, V: Searchable> Searchable for BTreeMap<K, V> {
+    fn index(&self, indexer: &mut NgramIndexer) {
+        for value in self.values() {
+            value.index(indexer);
+        }
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher
+            .aggregation()
+            .aggregate(self.values().filter_map(|value| value.get_score(searcher)))
+    }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        best_match(
+            self.values().filter_map(|value| value.get_match(searcher)),
+            searcher.aggregation(),
+        )
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        for value in self.values() {
+            value.write_text(buffer);
         }
     }
 }
@@ -51,9 +219,19 @@ impl<T: Searchable> Searchable for Option<T> {
         }
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
         self.as_ref().and_then(|item| item.get_score(searcher))
     }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        self.as_ref().and_then(|item| item.get_match(searcher))
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        if let Some(item) = self {
+            item.write_text(buffer);
+        }
+    }
 }
 
 impl<T: Searchable> Searchable for Rc<T> {
@@ -61,9 +239,17 @@ impl<T: Searchable> Searchable for Rc<T> {
         (**self).index(indexer);
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
         (**self).get_score(searcher)
     }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        (**self).get_match(searcher)
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        (**self).write_text(buffer);
+    }
 }
 
 impl<T: Searchable> Searchable for Arc<T> {
@@ -71,9 +257,17 @@ impl<T: Searchable> Searchable for Arc<T> {
         (**self).index(indexer);
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
         (**self).get_score(searcher)
     }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        (**self).get_match(searcher)
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        (**self).write_text(buffer);
+    }
 }
 
 impl<T: Searchable> Searchable for Box<T> {
@@ -81,7 +275,147 @@ impl<T: Searchable> Searchable for Box<T> {
         (**self).index(indexer);
     }
 
-    fn get_score(&self, searcher: &BitapSearcher) -> Option<f32> {
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
         (**self).get_score(searcher)
     }
+
+    fn get_match(&self, searcher: &dyn Scorer) -> Option<BitapMatch> {
+        (**self).get_match(searcher)
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        (**self).write_text(buffer);
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> Searchable for chrono::DateTime<Tz>
+where
+    Tz::Offset: core::fmt::Display,
+{
+    fn index(&self, indexer: &mut NgramIndexer) {
+        indexer.index(&self.to_rfc3339());
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher.get_score(&self.to_rfc3339())
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, &self.to_rfc3339());
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Searchable for chrono::NaiveDate {
+    fn index(&self, indexer: &mut NgramIndexer) {
+        indexer.index(&self.to_string());
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher.get_score(&self.to_string())
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, &self.to_string());
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Searchable for chrono::NaiveDateTime {
+    fn index(&self, indexer: &mut NgramIndexer) {
+        indexer.index(&self.to_string());
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher.get_score(&self.to_string())
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, &self.to_string());
+    }
+}
+
+#[cfg(feature = "time")]
+impl Searchable for time::OffsetDateTime {
+    fn index(&self, indexer: &mut NgramIndexer) {
+        indexer.index(&self.to_string());
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher.get_score(&self.to_string())
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, &self.to_string());
+    }
+}
+
+#[cfg(feature = "time")]
+impl Searchable for time::Date {
+    fn index(&self, indexer: &mut NgramIndexer) {
+        indexer.index(&self.to_string());
+    }
+
+    fn get_score(&self, searcher: &dyn Scorer) -> Option<f32> {
+        searcher.get_score(&self.to_string())
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        push_text(buffer, &self.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn field(weight: f32, score: f32) -> FieldScore {
+        FieldScore {
+            label: None,
+            weight,
+            score,
+            range: None,
+        }
+    }
+
+    #[test]
+    fn combine_aggregates_weighted_scores() {
+        let scores = vec![field(1.0, 0.5), field(0.5, 0.8), field(0.25, 1.0)];
+
+        // The weighted scores are 0.5, 0.4 and 0.25
+        assert_eq!(
+            FieldScore::combine(&scores, ScoreAggregation::Max),
+            Some(0.5)
+        );
+        assert_eq!(
+            FieldScore::combine(&scores, ScoreAggregation::Sum),
+            Some(1.15)
+        );
+        assert_eq!(
+            FieldScore::combine(&scores, ScoreAggregation::Mean),
+            Some(1.15 / 3.0)
+        );
+    }
+
+    #[test]
+    fn combine_without_matches_is_none() {
+        assert_eq!(FieldScore::combine(&[], ScoreAggregation::Max), None);
+        assert_eq!(FieldScore::combine(&[], ScoreAggregation::Sum), None);
+    }
+
+    #[test]
+    fn zero_weight_fields_contribute_nothing() {
+        let scores = vec![field(0.0, 1.0), field(1.0, 0.3)];
+
+        assert_eq!(
+            FieldScore::combine(&scores, ScoreAggregation::Max),
+            Some(0.3)
+        );
+        assert_eq!(
+            FieldScore::combine(&scores, ScoreAggregation::Sum),
+            Some(0.3)
+        );
+    }
 }
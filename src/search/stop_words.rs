@@ -0,0 +1,16 @@
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+
+/// Common English words carrying little meaning on their own, see [`english`].
+pub const ENGLISH: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have", "he",
+    "her", "his", "i", "if", "in", "into", "is", "it", "its", "my", "no", "not", "of", "on", "or",
+    "our", "she", "so", "that", "the", "their", "them", "then", "there", "these", "they", "this",
+    "to", "was", "we", "were", "what", "when", "which", "who", "will", "with", "you", "your",
+];
+
+/// Returns the [`ENGLISH`] stop words, ready to use as
+/// [`SearchConfig::stop_words`](crate::search::SearchConfig::stop_words).
+pub fn english() -> BTreeSet<String> {
+    ENGLISH.iter().map(ToString::to_string).collect()
+}
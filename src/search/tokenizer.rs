@@ -0,0 +1,18 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Splits the text into lowercase words, using any non-alphanumeric character as a separator.
+/// Punctuation is stripped, so `"Note, first-draft!"` gives `["note", "first", "draft"]`.
+///
+/// Use this in index generators to share the tokenization used by the search engine.
+pub fn tokenize(text: &str) -> Vec<String> {
+    words(text).map(str::to_lowercase).collect()
+}
+
+/// Splits the text into words like [`tokenize`], keeping their case.
+/// Used by the search engine for [`Tokenization::Words`](crate::search::Tokenization::Words),
+/// stop words and the terms of `SearchEngine::search_terms`, where the case is left to the normalizer.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
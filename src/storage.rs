@@ -0,0 +1,231 @@
+use crate::entities::Entity;
+use crate::ids::Id;
+use crate::tables::Entry;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::ops::Bound;
+
+/// The entries returned by [`Storage::iter`] and [`Storage::range`], sorted by ID.
+pub type StorageIter<'a, T, E> = Box<dyn DoubleEndedIterator<Item = Result<Entry<T>, E>> + 'a>;
+
+/// Where a [`Table`](crate::tables::Table) keeps its entries, sorted by ID,
+/// in memory or elsewhere, like a file or an embedded database.
+///
+/// Entries are returned owned, so storages reading from elsewhere can decode them on the fly,
+/// and every method can fail with the error of the storage.
+///
+/// The table writes each change through to its storage before applying it, and reads
+/// the entries once, when created with [`Table::with_storage`](crate::tables::Table::with_storage).
+/// It then serves lookups, indices and searches from memory, like with the default storage,
+/// keeping its own copy of the entries unless the storage lends them with [`Storage::memory`].
+/// When the storage fails, the table is left as it was before the change, except for
+/// changes made of several writes, like [`Table::clear`](crate::tables::Table::clear),
+/// which keep the writes made before the failure.
+///
+/// [`MemoryStorage`], keeping entries in a `BTreeMap`, is used by default.
+pub trait Storage<T: Entity> {
+    /// The error returned when the storage fails, reported by the table
+    /// as [`Error::Storage`](crate::Error::Storage).
+    type Error: Display;
+
+    /// Returns the entry with the given ID.
+    fn get(&self, id: &Id<T>) -> Result<Option<Entry<T>>, Self::Error>;
+
+    /// Stores the entry under its ID, returning the entry it replaced.
+    fn insert(&mut self, entry: Entry<T>) -> Result<Option<Entry<T>>, Self::Error>;
+
+    /// Removes the entry with the given ID, returning it.
+    fn remove(&mut self, id: &Id<T>) -> Result<Option<Entry<T>>, Self::Error>;
+
+    /// Returns the number of stored entries.
+    fn len(&self) -> Result<usize, Self::Error>;
+
+    /// Iterates over every entry, sorted by ID.
+    fn iter(&self) -> StorageIter<'_, T, Self::Error>;
+
+    /// Iterates over the entries whose IDs fall within the bounds, sorted by ID.
+    fn range(&self, range: (Bound<&Id<T>>, Bound<&Id<T>>)) -> StorageIter<'_, T, Self::Error>;
+
+    /// Returns whether an entry with the given ID is stored.
+    fn contains(&self, id: &Id<T>) -> Result<bool, Self::Error> {
+        Ok(self.get(id)?.is_some())
+    }
+
+    /// Returns whether no entry is stored.
+    fn is_empty(&self) -> Result<bool, Self::Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Returns every stored entry when the storage keeps all of them in memory,
+    /// so the table reads them from it instead of keeping a copy.
+    /// Returns `None` by default, and must return the same variant on every call.
+    fn memory(&self) -> Option<&BTreeMap<Id<T>, Entry<T>>> {
+        None
+    }
+}
+
+/// The default storage of tables, keeping entries in memory in a `BTreeMap`.
+#[derive(Debug)]
+pub struct MemoryStorage<T: Entity> {
+    pub(crate) entries: BTreeMap<Id<T>, Entry<T>>,
+}
+
+impl<T: Entity> Default for MemoryStorage<T> {
+    fn default() -> Self {
+        MemoryStorage {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+/// Clones the map of entries, sharing the entities with the clone.
+impl<T: Entity> Clone for MemoryStorage<T> {
+    fn clone(&self) -> Self {
+        MemoryStorage {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<T: Entity> Storage<T> for MemoryStorage<T> {
+    type Error = Infallible;
+
+    fn get(&self, id: &Id<T>) -> Result<Option<Entry<T>>, Infallible> {
+        Ok(self.entries.get(id).cloned())
+    }
+
+    fn insert(&mut self, entry: Entry<T>) -> Result<Option<Entry<T>>, Infallible> {
+        Ok(self.entries.insert(entry.get_id().clone(), entry))
+    }
+
+    fn remove(&mut self, id: &Id<T>) -> Result<Option<Entry<T>>, Infallible> {
+        Ok(self.entries.remove(id))
+    }
+
+    fn len(&self) -> Result<usize, Infallible> {
+        Ok(self.entries.len())
+    }
+
+    fn iter(&self) -> StorageIter<'_, T, Infallible> {
+        Box::new(self.entries.values().cloned().map(Ok))
+    }
+
+    fn range(&self, range: (Bound<&Id<T>>, Bound<&Id<T>>)) -> StorageIter<'_, T, Infallible> {
+        Box::new(
+            self.entries
+                .range::<Id<T>, _>(range)
+                .map(|(_, entry)| Ok(entry.clone())),
+        )
+    }
+
+    fn contains(&self, id: &Id<T>) -> Result<bool, Infallible> {
+        Ok(self.entries.contains_key(id))
+    }
+
+    fn memory(&self) -> Option<&BTreeMap<Id<T>, Entry<T>>> {
+        Some(&self.entries)
+    }
+}
+
+/// The entries of a table, read from memory and written through to its storage.
+pub(crate) struct Entries<T: Entity, S: Storage<T>> {
+    storage: S,
+    /// Every entry, unless the storage keeps them in memory itself.
+    cache: BTreeMap<Id<T>, Entry<T>>,
+}
+
+impl<T: Entity> Entries<T, MemoryStorage<T>> {
+    /// Keeps the given entries in memory.
+    pub(crate) fn in_memory(entries: BTreeMap<Id<T>, Entry<T>>) -> Self {
+        Entries {
+            storage: MemoryStorage { entries },
+            cache: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Entity, S: Storage<T>> Entries<T, S> {
+    /// Reads every entry of the storage, unless it keeps them in memory itself.
+    pub(crate) fn load(storage: S) -> Result<Self, S::Error> {
+        let cache = match storage.memory() {
+            Some(_) => BTreeMap::new(),
+            None => storage
+                .iter()
+                .map(|entry| entry.map(|entry| (entry.get_id().clone(), entry)))
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(Entries { storage, cache })
+    }
+
+    /// Returns the storage the entries are written to.
+    pub(crate) fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Returns every entry by ID.
+    pub(crate) fn map(&self) -> &BTreeMap<Id<T>, Entry<T>> {
+        self.storage.memory().unwrap_or(&self.cache)
+    }
+
+    pub(crate) fn get(&self, id: &Id<T>) -> Option<&Entry<T>> {
+        self.map().get(id)
+    }
+
+    pub(crate) fn contains(&self, id: &Id<T>) -> bool {
+        self.map().contains_key(id)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.map().len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.map().is_empty()
+    }
+
+    /// Iterates over every entry, sorted by ID.
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = &Entry<T>> {
+        self.map().values()
+    }
+
+    /// Iterates over the entries whose IDs fall within the bounds, sorted by ID.
+    pub(crate) fn range<'a>(
+        &'a self,
+        range: (Bound<&Id<T>>, Bound<&Id<T>>),
+    ) -> impl DoubleEndedIterator<Item = &'a Entry<T>> + use<'a, T, S> {
+        self.map().range::<Id<T>, _>(range).map(|(_, entry)| entry)
+    }
+
+    /// Stores the entry in the storage, then in memory, returning the entry it replaced.
+    pub(crate) fn insert(&mut self, entry: Entry<T>) -> Result<Option<Entry<T>>, S::Error> {
+        let replaced = self.storage.insert(entry.clone())?;
+
+        if self.storage.memory().is_some() {
+            return Ok(replaced);
+        }
+
+        Ok(self.cache.insert(entry.get_id().clone(), entry))
+    }
+
+    /// Removes the entry with the given ID from the storage, then from memory, returning it.
+    pub(crate) fn remove(&mut self, id: &Id<T>) -> Result<Option<Entry<T>>, S::Error> {
+        let removed = self.storage.remove(id)?;
+
+        if self.storage.memory().is_some() {
+            return Ok(removed);
+        }
+
+        Ok(self.cache.remove(id))
+    }
+}
+
+impl<T: Entity, S: Storage<T> + Clone> Clone for Entries<T, S> {
+    fn clone(&self) -> Self {
+        Entries {
+            storage: self.storage.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
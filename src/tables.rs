@@ -1,174 +1,1474 @@
 use crate::Error;
+#[cfg(feature = "bincode")]
+use crate::SnapshotError;
 use crate::entities::Entity;
 use crate::ids::Id;
-use crate::indices::Indexer;
-use crate::search::{SearchConfig, SearchEngine, SearchResult, Searchable};
+use crate::indices::{Indexer, KeyedIndexer};
+use crate::search::{
+    JaroWinklerScorer, MaybeSendSync, QueryIssue, QueryMode, RankedResult, SearchConfig,
+    SearchEngine, SearchOutcome, SearchResult, Searchable, TieBreak,
+};
+use crate::storage::{Entries, MemoryStorage, Storage};
 use std::any::TypeId;
-use std::collections::{BTreeMap, HashMap};
-use std::ops::Deref;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap, HashSet, hash_map};
+use std::hash::{Hash, Hasher};
+use std::ops::{Bound, Deref, RangeBounds};
+#[cfg(feature = "bincode")]
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-/// A table that stores entities in a BTreeMap.
+/// A table that stores entities in a [`Storage`], a `BTreeMap` in memory by default.
 /// It provides basic CRUD operations and supports fuzzy text search through a search engine.
-pub struct Table<T: Entity> {
-    /// For now, we use a BTreeMap for simplicity.
-    entities: BTreeMap<Id<T>, Entry<T>>,
-    search_engine: Arc<Mutex<Option<SearchEngine<T>>>>,
+pub struct Table<T: Entity, S: Storage<T> = MemoryStorage<T>> {
+    entities: Entries<T, S>,
+    search_engine: Arc<RwLock<SearchState<T>>>,
+    search_config: SearchConfig,
     indices: HashMap<TypeId, Box<dyn Indexer<Entity = T> + Send + Sync>>,
+    /// Timestamps of each entry, only when enabled with [`TableConfig::track_metadata`].
+    metadata: Option<BTreeMap<Id<T>, EntryMeta>>,
+    /// Persisted indices loaded with the table, by name, waiting for their indexer to be added.
+    /// Dropped on the first change, as they would be out of date.
+    #[cfg(feature = "bincode")]
+    persisted_indices: HashMap<String, Vec<u8>>,
+    /// Callbacks registered with [`Table::on_change`], called after each change.
+    observers: Vec<Observer<T>>,
+    /// The time source for entity timestamps and metadata, see [`TableConfig::clock`].
+    clock: Clock,
+}
+
+/// The time source of a table, see [`TableConfig::clock`].
+/// Shared between a table and its clones.
+pub type Clock = Arc<dyn Fn() -> u64 + Send + Sync>;
+
+/// A callback registered with [`Table::on_change`].
+pub type Observer<T> = Box<dyn Fn(&ChangeEvent<T>) + Send + Sync>;
+
+/// Configuration used to create a [`Table`] with [`Table::new`].
+/// It bundles the search configuration and the indices to register up front.
+pub struct TableConfig<T: Entity> {
+    search: SearchConfig,
+    indices: Vec<(TypeId, Box<dyn Indexer<Entity = T> + Send + Sync>)>,
+    track_metadata: bool,
+    clock: Clock,
+}
+
+impl<T: Entity + 'static> TableConfig<T> {
+    /// Creates a new configuration with the default search configuration and no indices.
+    pub fn new() -> Self {
+        TableConfig {
+            search: SearchConfig::default(),
+            indices: Vec::new(),
+            track_metadata: false,
+            clock: Arc::new(EntryMeta::now),
+        }
+    }
+
+    /// Sets the search configuration used when building the table's search engine.
+    pub fn search(mut self, config: SearchConfig) -> Self {
+        self.search = config;
+        self
+    }
+
+    /// Registers an indexer to be added to the table on creation.
+    /// Only the first indexer of each type is kept, like with [`Table::add_index`].
+    pub fn index<I: Indexer<Entity = T> + Send + Sync + 'static>(mut self, indexer: I) -> Self {
+        self.indices.push((TypeId::of::<I>(), Box::new(indexer)));
+        self
+    }
+
+    /// Enables recording when each entry was created and last updated, see [`Table::metadata`].
+    pub fn track_metadata(mut self, track_metadata: bool) -> Self {
+        self.track_metadata = track_metadata;
+        self
+    }
+
+    /// Sets the clock giving the current time to entity timestamps (see [`Entity::on_insert`])
+    /// and metadata, to inject a fixed time in tests.
+    /// Defaults to the wall clock, in milliseconds since the Unix epoch.
+    pub fn clock<F: Fn() -> u64 + Send + Sync + 'static>(mut self, clock: F) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl<T: Entity + 'static> Default for TableConfig<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: Entity + 'static> Table<T> {
+    /// Creates a new empty table in memory with the given configuration.
+    /// Use [`Table::with_storage`] for tables using another storage.
+    pub fn new(config: TableConfig<T>) -> Self {
+        Self::with_entries(Entries::in_memory(BTreeMap::new()), config)
+            .expect("An empty table cannot break a unique index")
+    }
+}
+
+impl<T: Entity + 'static, S: Storage<T>> Table<T, S> {
+    /// Creates a table over the given storage, reading the entries it already holds
+    /// and indexing them. Fails with [`Error::Storage`] if they cannot be read,
+    /// and with [`Error::UniqueViolation`] if they share a key of a unique index of `config`.
+    pub fn with_storage(storage: S, config: TableConfig<T>) -> Result<Self, Error> {
+        let entities = Entries::load(storage).map_err(Self::storage_error)?;
+        Self::with_entries(entities, config)
+    }
+
+    /// Creates a table over the given entries, indexing them like with [`Table::add_index`].
+    fn with_entries(entities: Entries<T, S>, config: TableConfig<T>) -> Result<Self, Error> {
+        let TableConfig {
+            search,
+            indices,
+            track_metadata,
+            clock,
+        } = config;
+
+        let mut table = Table {
+            entities,
+            search_engine: Arc::default(),
+            search_config: search,
+            indices: HashMap::new(),
+            metadata: track_metadata.then(BTreeMap::new),
+            #[cfg(feature = "bincode")]
+            persisted_indices: HashMap::new(),
+            observers: Vec::new(),
+            clock,
+        };
+
+        for (type_id, mut indexer) in indices {
+            if let hash_map::Entry::Vacant(vacant) = table.indices.entry(type_id) {
+                Self::index_checked(indexer.as_mut(), &table.entities)?;
+                vacant.insert(indexer);
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Returns the storage of the table, for instance to flush it.
+    /// Changes have to go through the table, to keep its indices and search engine in sync.
+    pub fn storage(&self) -> &S {
+        self.entities.storage()
+    }
+
     /// Inserts a new entity into the table, returning a reference to the entry.
-    pub fn insert(&mut self, entity: T) -> Result<&Entry<T>, Error> {
+    ///
+    /// The returned reference borrows the table mutably, so it must be dropped before using
+    /// the table again. To keep the entry around, clone it (this only clones an `Arc`),
+    /// or use [`Table::insert_id`] when only the ID is needed.
+    pub fn insert(&mut self, mut entity: T) -> Result<&Entry<T>, Error> {
+        self.check_insert(&entity)?;
+
+        let now = self.now();
+        entity.on_insert(now);
+
+        let id = entity.get_id().clone();
         let entry = Entry {
             entity: Arc::new(entity),
         };
 
-        let id = entry.get_id().clone();
+        self.check_indices(&entry)?;
+        self.store(entry, now)?;
+
+        Ok(self.entities.get(&id).unwrap())
+    }
+
+    /// Inserts a batch of new entities, indexing them in a single pass.
+    ///
+    /// The whole batch is checked before changing anything: if any entity is invalid,
+    /// or if IDs already exist in the table or repeat within the batch, the table is left untouched.
+    /// Duplicates are reported together in [`Error::DuplicateIds`].
+    /// If an entity breaks a unique index, the entities stored before it are removed again.
+    pub fn insert_many(&mut self, mut entities: Vec<T>) -> Result<Vec<&Entry<T>>, Error> {
+        let mut seen = HashSet::with_capacity(entities.len());
+        let mut duplicates = Vec::new();
+
+        for entity in &entities {
+            Self::validate(entity)?;
+
+            let id = entity.get_id();
+
+            if self.entities.contains(id) || !seen.insert(id) {
+                duplicates.push(id.value().to_string());
+            }
+        }
+
+        if !duplicates.is_empty() {
+            return Err(Error::DuplicateIds(duplicates, std::any::type_name::<T>()));
+        }
+
+        let now = self.now();
+
+        for entity in &mut entities {
+            entity.on_insert(now);
+        }
+
+        let ids = entities
+            .iter()
+            .map(|entity| entity.get_id().clone())
+            .collect::<Vec<_>>();
+
+        self.store_all(
+            entities
+                .into_iter()
+                .map(|entity| Entry {
+                    entity: Arc::new(entity),
+                })
+                .collect(),
+            now,
+        )?;
+
+        Ok(ids
+            .iter()
+            .map(|id| self.entities.get(id).unwrap())
+            .collect())
+    }
+
+    /// Inserts a new entity into the table, reporting its side effects on indices
+    /// and the search engine.
+    pub fn insert_detailed(&mut self, mut entity: T) -> Result<InsertOutcome<T>, Error> {
+        self.check_insert(&entity)?;
 
-        if self.entities.contains_key(&id) {
+        let now = self.now();
+        entity.on_insert(now);
+
+        let id = entity.get_id().clone();
+        let entry = Entry {
+            entity: Arc::new(entity),
+        };
+        let mut index_keys = Vec::with_capacity(self.indices.len());
+
+        self.check_indices(&entry)?;
+        self.store_with(entry, now, |name, count| index_keys.push((name, count)))?;
+
+        index_keys.sort_unstable_by_key(|&(name, _)| name);
+
+        Ok(InsertOutcome {
+            id,
+            index_keys,
+            search_invalidated: self.search_engine_built(),
+        })
+    }
+
+    /// Checks that the entity is valid and that its ID is not taken yet.
+    fn check_insert(&self, entity: &T) -> Result<(), Error> {
+        Self::validate(entity)?;
+
+        if self.entities.contains(entity.get_id()) {
             return Err(Error::EntityAlreadyExists(
+                entity.get_id().value().to_string(),
+                std::any::type_name::<T>(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a new entity into the table, returning its ID without borrowing the table.
+    pub fn insert_id(&mut self, entity: T) -> Result<Id<T>, Error> {
+        self.insert(entity).map(|entry| entry.get_id().clone())
+    }
+
+    /// Inserts a new entity into the table under a freshly generated ULID,
+    /// replacing its current ID, and returns the assigned ID.
+    #[cfg(feature = "ulid")]
+    pub fn insert_with_generated_id(&mut self, mut entity: T) -> Result<Id<T>, Error> {
+        entity.set_id(Id::new_ulid());
+        self.insert_id(entity)
+    }
+
+    /// Returns the number of entities in the table, including soft-deleted ones.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns whether the table has no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Returns whether an entity with the given ID is in the table.
+    pub fn contains(&self, id: &Id<T>) -> bool {
+        self.entities.contains(id)
+    }
+
+    /// Returns an iterator over all entries in the table, skipping soft-deleted ones.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry<T>> {
+        self.entities.iter().filter(|entry| !entry.is_deleted())
+    }
+
+    /// Returns an iterator over all entries in the table, soft-deleted ones included.
+    pub fn iter_including_deleted(&self) -> impl Iterator<Item = &Entry<T>> {
+        self.entities.iter()
+    }
+
+    /// Returns the entries whose IDs fall inside the range, sorted by ID, skipping soft-deleted ones.
+    /// IDs are ordered lexicographically over their string value, so ULIDs are ordered by time.
+    pub fn range<R: RangeBounds<Id<T>>>(&self, range: R) -> impl Iterator<Item = &Entry<T>> {
+        self.entities
+            .range((range.start_bound(), range.end_bound()))
+            .filter(|entry| !entry.is_deleted())
+    }
+
+    /// Returns the next `limit` entries after the ID `after`, or from the start when `None`,
+    /// sorted by ID and skipping soft-deleted ones.
+    /// Pass the returned [`Page::cursor`] as `after` to get the following page.
+    pub fn page_after(&self, after: Option<&Id<T>>, limit: usize) -> Page<T> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut entries = self.range((start, Bound::Unbounded));
+        let page = entries.by_ref().take(limit).cloned().collect::<Vec<_>>();
+
+        Page {
+            cursor: page.last().map(|entry| entry.get_id().clone()),
+            has_more: entries.next().is_some(),
+            entries: page,
+        }
+    }
+
+    /// Returns the entry with the smallest ID, following the lexicographic order of IDs.
+    pub fn first(&self) -> Option<&Entry<T>> {
+        self.iter().next()
+    }
+
+    /// Returns the entry with the largest ID, following the lexicographic order of IDs.
+    pub fn last(&self) -> Option<&Entry<T>> {
+        self.entities.iter().rev().find(|entry| !entry.is_deleted())
+    }
+
+    /// Finds an entry in the table by its ID, soft-deleted or not.
+    pub fn find(&self, id: &Id<T>) -> Option<&Entry<T>> {
+        self.entities.get(id)
+    }
+
+    /// Finds the entries with the given IDs, soft-deleted or not, in the order they are given.
+    /// Missing entries are `None`, see [`Table::get_many_existing`] to skip them instead.
+    pub fn get_many<'a>(
+        &'a self,
+        ids: impl IntoIterator<Item = &'a Id<T>>,
+    ) -> Vec<Option<&'a Entry<T>>> {
+        ids.into_iter().map(|id| self.find(id)).collect()
+    }
+
+    /// Finds the entries with the given IDs, soft-deleted or not, in the order they are given,
+    /// skipping the missing ones.
+    pub fn get_many_existing<'a>(
+        &'a self,
+        ids: impl IntoIterator<Item = &'a Id<T>>,
+    ) -> Vec<&'a Entry<T>> {
+        ids.into_iter().filter_map(|id| self.find(id)).collect()
+    }
+
+    /// Finds the entry with the given ID, then follows the ID of `key` to its entry
+    /// in the `related` table, e.g. the author of a note:
+    /// `notes.get_related(&note_id, &users, |note| &note.created_by)`.
+    /// Returns `None` if either entry is missing.
+    pub fn get_related<'a, U: Entity + 'static, R: Storage<U>, F: FnOnce(&T) -> &Id<U>>(
+        &self,
+        id: &Id<T>,
+        related: &'a Table<U, R>,
+        key: F,
+    ) -> Option<&'a Entry<U>> {
+        self.find(id).and_then(|entry| related.find(key(entry)))
+    }
+
+    /// Returns the entries whose ID returned by `key` is `id`, sorted by ID,
+    /// e.g. the notes written by a user: `notes.find_referencing(&user_id, |note| &note.created_by)`.
+    /// Scans the whole table; declare an index for lookups done often.
+    pub fn find_referencing<U: Entity, F: Fn(&T) -> &Id<U>>(
+        &self,
+        id: &Id<U>,
+        key: F,
+    ) -> Vec<&Entry<T>> {
+        self.iter().filter(|entry| key(entry) == id).collect()
+    }
+
+    /// Returns the entries matching the predicate, sorted by ID.
+    /// Scans the whole table; declare an index for lookups done often.
+    pub fn filter<F: Fn(&Entry<T>) -> bool>(&self, predicate: F) -> Vec<&Entry<T>> {
+        self.iter().filter(|entry| predicate(entry)).collect()
+    }
+
+    /// Returns the entry with the smallest ID matching the predicate.
+    pub fn find_one_by<F: Fn(&Entry<T>) -> bool>(&self, predicate: F) -> Option<&Entry<T>> {
+        self.iter().find(|entry| predicate(entry))
+    }
+
+    /// Returns the IDs from `ids` that are present in the table, in the order they are given.
+    pub fn existing_ids<'a>(&'a self, ids: impl IntoIterator<Item = &'a Id<T>>) -> Vec<&'a Id<T>> {
+        ids.into_iter()
+            .filter(|id| self.entities.contains(*id))
+            .collect()
+    }
+
+    /// Finds an entry in the table by its ID, ignoring it if it is expired at `now`.
+    pub fn find_unexpired(&self, id: &Id<T>, now: u64) -> Option<&Entry<T>> {
+        self.find(id).filter(|entry| !entry.is_expired(now))
+    }
+
+    /// Updates an existing entity in the table, returning a reference to the updated entry.
+    pub fn update(&mut self, mut entity: T) -> Result<&Entry<T>, Error> {
+        Self::validate(&entity)?;
+
+        let id = entity.get_id().clone();
+
+        if !self.entities.contains(&id) {
+            return Err(Error::EntityNotFound(
+                id.value().to_string(),
+                std::any::type_name::<T>(),
+            ));
+        }
+
+        let now = self.now();
+        entity.on_update(now);
+
+        let entry = Entry {
+            entity: Arc::new(entity),
+        };
+
+        self.check_indices(&entry)?;
+        self.store(entry, now)?;
+
+        Ok(self.entities.get(&id).unwrap())
+    }
+
+    /// Inserts the entity if its ID is absent, or updates the existing one otherwise,
+    /// returning the entry along with which of the two happened.
+    pub fn upsert(&mut self, mut entity: T) -> Result<(&Entry<T>, Upserted), Error> {
+        Self::validate(&entity)?;
+
+        let id = entity.get_id().clone();
+        let now = self.now();
+
+        if self.entities.contains(&id) {
+            entity.on_update(now);
+        } else {
+            entity.on_insert(now);
+        }
+
+        let entry = Entry {
+            entity: Arc::new(entity),
+        };
+
+        self.check_indices(&entry)?;
+
+        let upserted = match self.store(entry, now)? {
+            Some(_) => Upserted::Updated,
+            None => Upserted::Inserted,
+        };
+
+        Ok((self.entities.get(&id).unwrap(), upserted))
+    }
+
+    /// Registers a callback called with every change made to the table, after the change is applied.
+    /// Callbacks are called in the order they were registered.
+    /// Batch operations like [`Table::insert_many`] only report their changes once all of them succeed.
+    pub fn on_change(&mut self, callback: Observer<T>) {
+        self.observers.push(callback);
+    }
+
+    /// Returns when the entry with the given ID was created and last updated.
+    /// Always `None` unless enabled with [`TableConfig::track_metadata`].
+    pub fn metadata(&self, id: &Id<T>) -> Option<EntryMeta> {
+        self.metadata.as_ref()?.get(id).copied()
+    }
+
+    /// Stores the entry, replacing any entry with the same ID, and keeps indices and metadata in sync.
+    /// The entity is marked as changed for the search engine. Returns the replaced entry.
+    /// `now` is recorded in the metadata, read once per operation so it matches the timestamps
+    /// set on the entity.
+    /// Fails with [`Error::Storage`] without changing anything if the storage fails.
+    fn store(&mut self, entry: Entry<T>, now: u64) -> Result<Option<Entry<T>>, Error> {
+        self.store_with(entry, now, |_, _| {})
+    }
+
+    /// Stores the entries like [`Table::store`], checking each one against the indices first.
+    /// If an entry is rejected or the storage fails, the entries stored before it are reverted
+    /// and the error is returned.
+    /// Observers are only notified once every entry is stored.
+    fn store_all(&mut self, entries: Vec<Entry<T>>, now: u64) -> Result<(), Error> {
+        let observers = std::mem::take(&mut self.observers);
+        #[cfg(feature = "bincode")]
+        let persisted_indices = std::mem::take(&mut self.persisted_indices);
+        let mut stored = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let id = entry.get_id().clone();
+            let meta = self.metadata(&id);
+
+            let replaced = match self
+                .check_indices(&entry)
+                .and_then(|()| self.store(entry, now))
+            {
+                Ok(replaced) => replaced,
+                Err(error) => {
+                    // A storage failure while reverting takes precedence, as the table changed
+                    let reverted = stored
+                        .into_iter()
+                        .rev()
+                        .try_for_each(|(id, replaced, meta)| self.revert(&id, replaced, meta));
+
+                    #[cfg(feature = "bincode")]
+                    {
+                        self.persisted_indices = persisted_indices;
+                    }
+
+                    self.observers = observers;
+                    return Err(reverted.err().unwrap_or(error));
+                }
+            };
+
+            stored.push((id, replaced, meta));
+        }
+
+        #[cfg(feature = "bincode")]
+        if stored.is_empty() {
+            self.persisted_indices = persisted_indices;
+        }
+
+        self.observers = observers;
+
+        for (id, replaced, _) in stored {
+            let entry = self.entities.get(&id).unwrap().clone();
+            self.notify(ChangeEvent::stored(replaced, entry));
+        }
+
+        Ok(())
+    }
+
+    /// Puts back the entry and metadata the entity with the given ID had before it was stored,
+    /// removing it if it was not in the table. Unlike [`Table::store`], the clock is not read
+    /// and observers are not notified, so reverting leaves no trace.
+    fn revert(
+        &mut self,
+        id: &Id<T>,
+        replaced: Option<Entry<T>>,
+        meta: Option<EntryMeta>,
+    ) -> Result<(), Error> {
+        let stored = match &replaced {
+            Some(replaced) => self.entities.insert(replaced.clone()),
+            None => self.entities.remove(id),
+        }
+        .map_err(Self::storage_error)?;
+
+        self.mark_search_stale(id);
+
+        for index in self.indices.values_mut() {
+            if let Some(stored) = &stored {
+                index.forget(stored);
+            }
+
+            if let Some(replaced) = replaced.as_ref().filter(|replaced| !replaced.is_deleted()) {
+                index.index(replaced);
+            }
+        }
+
+        if let Some(metadata) = &mut self.metadata {
+            match meta {
+                Some(meta) => metadata.insert(id.clone(), meta),
+                None => metadata.remove(id),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Calls every observer registered with [`Table::on_change`] with the event.
+    fn notify(&self, event: ChangeEvent<T>) {
+        for observer in &self.observers {
+            observer(&event);
+        }
+    }
+
+    /// Checks the entry against every index, failing with [`Error::UniqueViolation`]
+    /// if it would share a key of a unique index with another entity.
+    /// Soft-deleted entries are not indexed, so they are never rejected.
+    fn check_indices(&self, entry: &Entry<T>) -> Result<(), Error> {
+        if entry.is_deleted() {
+            return Ok(());
+        }
+
+        self.indices
+            .values()
+            .try_for_each(|index| index.check(entry))
+    }
+
+    /// Stores the entry like [`Table::store`], calling `on_index` with the name of each index
+    /// and the number of keys the entry was indexed under.
+    /// Soft-deleted entries are left out of indices, so index lookups skip them.
+    fn store_with<F: FnMut(&'static str, usize)>(
+        &mut self,
+        entry: Entry<T>,
+        now: u64,
+        mut on_index: F,
+    ) -> Result<Option<Entry<T>>, Error> {
+        let id = entry.get_id().clone();
+        let existing_entry = self
+            .entities
+            .insert(entry.clone())
+            .map_err(Self::storage_error)?;
+
+        #[cfg(feature = "bincode")]
+        self.persisted_indices.clear();
+
+        self.mark_search_stale(&id);
+
+        for index in self.indices.values_mut() {
+            // Remove the old entry from indices before indexing the new one
+            if let Some(existing_entry) = &existing_entry {
+                index.forget(existing_entry);
+            }
+
+            if entry.is_deleted() {
+                on_index(index.name(), 0);
+            } else {
+                on_index(index.name(), index.index_counted(&entry));
+            }
+        }
+
+        if let Some(metadata) = &mut self.metadata {
+            metadata
+                .entry(id)
+                .and_modify(|meta| meta.updated_at = now)
+                .or_insert(EntryMeta {
+                    created_at: now,
+                    updated_at: now,
+                });
+        }
+
+        if !self.observers.is_empty() {
+            self.notify(ChangeEvent::stored(existing_entry.clone(), entry));
+        }
+
+        Ok(existing_entry)
+    }
+
+    /// Removes the entry with the given ID from the table, its indices and metadata.
+    /// The entity is marked as changed for the search engine.
+    /// Fails with [`Error::Storage`] without changing anything if the storage fails.
+    fn unstore(&mut self, id: &Id<T>) -> Result<Option<Entry<T>>, Error> {
+        let Some(existing_entry) = self.entities.remove(id).map_err(Self::storage_error)? else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "bincode")]
+        self.persisted_indices.clear();
+
+        self.mark_search_stale(id);
+
+        for index in self.indices.values_mut() {
+            index.forget(&existing_entry);
+        }
+
+        if let Some(metadata) = &mut self.metadata {
+            metadata.remove(id);
+        }
+
+        if !self.observers.is_empty() {
+            self.notify(ChangeEvent::Deleted(existing_entry.clone()));
+        }
+
+        Ok(Some(existing_entry))
+    }
+
+    /// Wraps an error of the storage in [`Error::Storage`].
+    fn storage_error(error: S::Error) -> Error {
+        Error::Storage(error.to_string(), std::any::type_name::<T>())
+    }
+
+    /// Marks the entity with the given ID as changed, so it is updated in the search engine
+    /// on the next search. Nothing is recorded while the search engine is not built.
+    fn mark_search_stale(&mut self, id: &Id<T>) {
+        if let Ok(mut state) = self.search_engine.write() {
+            if state.engine.is_some() {
+                state.stale.insert(id.clone());
+            }
+
+            return;
+        }
+
+        // The lock is poisoned, start over with a fresh engine
+        self.search_engine = Arc::default();
+    }
+
+    /// Returns whether the search engine is built, so changes have to be applied to it.
+    fn search_engine_built(&self) -> bool {
+        self.search_engine
+            .read()
+            .is_ok_and(|state| state.engine.is_some())
+    }
+
+    /// Returns the current time of the table clock.
+    fn now(&self) -> u64 {
+        (self.clock)()
+    }
+
+    /// Runs the entity validation, wrapping a failure in [`Error::Validation`].
+    fn validate(entity: &T) -> Result<(), Error> {
+        entity
+            .validate()
+            .map_err(|message| Error::Validation(message, std::any::type_name::<T>()))
+    }
+
+    /// Checks that the entity still has the ID it is stored under, failing with [`Error::IdChanged`].
+    fn check_id(id: &Id<T>, entity: &T) -> Result<(), Error> {
+        if entity.get_id() != id {
+            return Err(Error::IdChanged(
+                id.value().to_string(),
+                entity.get_id().value().to_string(),
+                std::any::type_name::<T>(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes an entity from the table by its ID.
+    pub fn delete(&mut self, id: &Id<T>) -> Result<(), Error> {
+        self.remove(id).map(|_| ())
+    }
+
+    /// Deletes an entity from the table by its ID, returning the removed entry.
+    pub fn remove(&mut self, id: &Id<T>) -> Result<Entry<T>, Error> {
+        self.take(id)?.ok_or_else(|| {
+            Error::EntityNotFound(id.value().to_string(), std::any::type_name::<T>())
+        })
+    }
+
+    /// Deletes an entity from the table by its ID, handling the entries of `referencing`
+    /// whose ID returned by `key` points at it as set by `on_delete`:
+    /// with [`OnDelete::Restrict`], nothing is deleted if a live entry still references it,
+    /// failing with [`Error::ReferentialIntegrity`]; with [`OnDelete::Cascade`],
+    /// every referencing entry is removed too, soft-deleted or not.
+    /// Returns the number of referencing entries removed.
+    pub fn delete_referenced<U: Entity + 'static, R: Storage<U>, F: Fn(&U) -> &Id<T>>(
+        &mut self,
+        id: &Id<T>,
+        referencing: &mut Table<U, R>,
+        key: F,
+        on_delete: OnDelete,
+    ) -> Result<usize, Error> {
+        if !self.entities.contains(id) {
+            return Err(Error::EntityNotFound(
                 id.value().to_string(),
                 std::any::type_name::<T>(),
             ));
         }
 
-        for index in self.indices.values_mut() {
-            index.index(&entry);
+        let removed = match on_delete {
+            OnDelete::Restrict => {
+                if !referencing.find_referencing(id, &key).is_empty() {
+                    return Err(Error::ReferentialIntegrity(
+                        id.value().to_string(),
+                        std::any::type_name::<U>(),
+                        std::any::type_name::<T>(),
+                    ));
+                }
+
+                0
+            }
+            OnDelete::Cascade => referencing.take_where(|entry| key(entry) == id)?.len(),
+        };
+
+        self.delete(id)?;
+
+        Ok(removed)
+    }
+
+    /// Deletes an entity from the table by its ID if it exists.
+    /// Returns `true` if an entity was removed, `false` if it was already absent.
+    ///
+    /// An absent entity is not an error: the `Result` is there on purpose, to report
+    /// a storage failing to remove the entity with [`Error::Storage`].
+    /// The default [`MemoryStorage`] never fails.
+    pub fn remove_if_present(&mut self, id: &Id<T>) -> Result<bool, Error> {
+        Ok(self.take(id)?.is_some())
+    }
+
+    /// Removes an entry from the table and its indices.
+    fn take(&mut self, id: &Id<T>) -> Result<Option<Entry<T>>, Error> {
+        self.unstore(id)
+    }
+
+    /// Removes every entry matching the predicate from the table and its indices.
+    /// If the storage fails, the entries removed before stay removed.
+    fn take_where<F: FnMut(&Entry<T>) -> bool>(
+        &mut self,
+        mut predicate: F,
+    ) -> Result<Vec<Entry<T>>, Error> {
+        let ids = self
+            .entities
+            .iter()
+            .filter(|entry| predicate(entry))
+            .map(|entry| entry.get_id().clone())
+            .collect::<Vec<_>>();
+
+        let mut removed = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            if let Some(existing_entry) = self.unstore(&id)? {
+                removed.push(existing_entry);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Removes every entity from the table and its indices, keeping the indices and configuration.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.take_where(|_| true).map(|_| ())
+    }
+
+    /// Keeps only the entities matching the predicate, soft-deleted or not,
+    /// removing the others from the table and its indices in a single sweep.
+    /// Returns the removed entries, sorted by ID.
+    pub fn retain<F: FnMut(&Entry<T>) -> bool>(
+        &mut self,
+        mut predicate: F,
+    ) -> Result<Vec<Entry<T>>, Error> {
+        self.take_where(|entry| !predicate(entry))
+    }
+
+    /// Removes every entity expired at `now` (see [`Entity::expires_at`]),
+    /// returning the number of entities removed.
+    pub fn purge_expired(&mut self, now: u64) -> Result<usize, Error> {
+        self.take_where(|entry| entry.is_expired(now))
+            .map(|removed| removed.len())
+    }
+
+    /// Removes every soft-deleted entity (see [`Table::soft_delete`]),
+    /// returning the number of entities removed.
+    pub fn purge_deleted(&mut self) -> Result<usize, Error> {
+        self.take_where(|entry| entry.is_deleted())
+            .map(|removed| removed.len())
+    }
+
+    /// Adds an indexer to the table, allowing for indexed queries.
+    /// Returns `false` without touching the existing index if an indexer of the same type
+    /// is already registered, use [`Table::replace_index`] to overwrite it.
+    ///
+    /// Fails with [`Error::UniqueViolation`] if the index is unique and entities of the table
+    /// already share a key, in which case the index is not added.
+    pub fn add_index<I: Indexer<Entity = T> + Send + Sync + 'static>(
+        &mut self,
+        indexer: I,
+    ) -> Result<bool, Error> {
+        if self.indices.contains_key(&TypeId::of::<I>()) {
+            return Ok(false);
+        }
+
+        self.replace_index(indexer)?;
+        Ok(true)
+    }
+
+    /// Adds an indexer to the table, replacing any indexer of the same type.
+    /// Returns `true` if an existing indexer was replaced.
+    ///
+    /// If the table was loaded with a persisted index of the same type and has not changed since,
+    /// the index is restored from it instead of being rebuilt.
+    ///
+    /// Fails with [`Error::UniqueViolation`] if the index is unique and entities of the table
+    /// already share a key, in which case the existing indexer is kept.
+    pub fn replace_index<I: Indexer<Entity = T> + Send + Sync + 'static>(
+        &mut self,
+        mut indexer: I,
+    ) -> Result<bool, Error> {
+        #[cfg(feature = "bincode")]
+        let restored = self
+            .persisted_indices
+            .remove(indexer.name())
+            .is_some_and(|bytes| indexer.restore(&bytes, self.entities.map()));
+        #[cfg(not(feature = "bincode"))]
+        let restored = false;
+
+        if !restored {
+            Self::index_checked(&mut indexer, &self.entities)?;
+        }
+
+        Ok(self
+            .indices
+            .insert(TypeId::of::<I>(), Box::new(indexer))
+            .is_some())
+    }
+
+    /// Clears every index and indexes every entry again, for when the keys generated
+    /// from the entities may have changed, or after loading a table whose persisted indices
+    /// are no longer trusted.
+    pub fn rebuild_indices(&mut self) {
+        #[cfg(feature = "bincode")]
+        self.persisted_indices.clear();
+
+        for index in self.indices.values_mut() {
+            Self::reindex(index.as_mut(), &self.entities);
+        }
+    }
+
+    /// Clears the index of type `I` and indexes every entry again.
+    /// Returns `false` if no index of this type is registered.
+    pub fn rebuild_index<I: Indexer<Entity = T> + 'static>(&mut self) -> bool {
+        let Some(index) = self.indices.get_mut(&TypeId::of::<I>()) else {
+            return false;
+        };
+
+        Self::reindex(index.as_mut(), &self.entities);
+        true
+    }
+
+    /// Removes the index of type `I` from the table.
+    /// Returns `false` if no index of this type was registered.
+    pub fn remove_index<I: Indexer<Entity = T> + 'static>(&mut self) -> bool {
+        self.indices.remove(&TypeId::of::<I>()).is_some()
+    }
+
+    /// Indexes every entry in a new index, leaving out soft-deleted entries.
+    /// Fails with [`Error::UniqueViolation`] if the index is unique and entries share a key.
+    fn index_checked(
+        index: &mut dyn Indexer<Entity = T>,
+        entities: &Entries<T, S>,
+    ) -> Result<(), Error> {
+        for entry in entities.iter().filter(|entry| !entry.is_deleted()) {
+            index.check(entry)?;
+            index.index(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Clears the index and indexes every entry again, leaving out soft-deleted entries.
+    fn reindex(index: &mut dyn Indexer<Entity = T>, entities: &Entries<T, S>) {
+        index.clear();
+
+        for entry in entities.iter().filter(|entry| !entry.is_deleted()) {
+            index.index(entry);
+        }
+    }
+
+    /// Finds entries in the table by a specific index key.
+    pub fn get_index<I: Indexer<Entity = T> + 'static>(&self) -> Option<&I> {
+        let type_id = TypeId::of::<I>();
+
+        if let Some(index) = self.indices.get(&type_id) {
+            // Downcast index from &Box<dyn Indexer<Entity = T>> to &I
+            if let Some(index) = index.as_any().downcast_ref::<I>() {
+                return Some(index);
+            }
         }
 
-        self.entities.insert(id.clone(), entry);
+        None
+    }
 
-        // Reset search engine on insert
-        self.search_engine = Arc::new(Mutex::new(None));
+    /// Returns the index of type `I` mutably, to call its methods taking `&mut self`.
+    /// Changing the indexed entries directly can leave the index out of sync with the table,
+    /// which [`Table::rebuild_index`] fixes.
+    pub fn get_index_mut<I: Indexer<Entity = T> + 'static>(&mut self) -> Option<&mut I> {
+        self.indices
+            .get_mut(&TypeId::of::<I>())?
+            .as_any_mut()
+            .downcast_mut::<I>()
+    }
 
-        Ok(self.entities.get(&id).unwrap())
+    /// Finds entries by key in the index of type `I`.
+    /// Soft-deleted entries are not indexed, so they are skipped.
+    /// Returns an empty list if the index is not registered on the table.
+    ///
+    /// The key must have the key type of the index, so passing a key of another type
+    /// is a compile error rather than an empty result.
+    pub fn find_by_index<I: KeyedIndexer<Entity = T> + 'static>(
+        &self,
+        key: &I::Key,
+    ) -> Vec<&Entry<T>> {
+        self.get_index::<I>()
+            .map(|index| index.storage().get(key))
+            .unwrap_or_default()
     }
 
-    /// Returns an iterator over all entries in the table.
-    pub fn iter(&self) -> impl Iterator<Item = &Entry<T>> {
-        self.entities.values()
+    /// Finds the IDs of the entries with the given key in the index of type `I`.
+    /// Returns an empty list if the index is not registered on the table.
+    pub fn ids_by_index<I: KeyedIndexer<Entity = T> + 'static>(&self, key: &I::Key) -> Vec<&Id<T>> {
+        self.find_by_index::<I>(key)
+            .into_iter()
+            .map(|entry| entry.get_id())
+            .collect()
     }
 
-    /// Finds an entry in the table by its ID.
-    pub fn find(&self, id: &Id<T>) -> Option<&Entry<T>> {
-        self.entities.get(id)
+    /// Finds the single entry with the given key in the index of type `I`, for unique keys.
+    /// Returns `None` if the index is not registered on the table or has no entry for the key.
+    pub fn find_one_by_index<I: KeyedIndexer<Entity = T> + 'static>(
+        &self,
+        key: &I::Key,
+    ) -> Option<&Entry<T>> {
+        self.find_by_index::<I>(key).into_iter().next()
     }
+}
 
-    /// Updates an existing entity in the table, returning a reference to the updated entry.
-    pub fn update(&mut self, entity: T) -> Result<&Entry<T>, Error> {
-        let id = entity.get_id().clone();
-        let Some(existing_entry) = self.entities.get(&id) else {
+impl<T: Entity + Clone + 'static, S: Storage<T>> Table<T, S> {
+    /// Applies `f` to a copy of the entity with the given ID and stores it back,
+    /// returning a reference to the updated entry.
+    /// Fails without changing anything if `f` changes the entity ID or makes the entity invalid,
+    /// so the stored key and the entity ID can never disagree.
+    pub fn modify<F: FnOnce(&mut T)>(&mut self, id: &Id<T>, f: F) -> Result<&Entry<T>, Error> {
+        let Some(entry) = self.entities.get(id) else {
             return Err(Error::EntityNotFound(
                 id.value().to_string(),
                 std::any::type_name::<T>(),
             ));
         };
 
-        // Remove the old entry from indices
-        for index in self.indices.values_mut() {
-            index.forget(existing_entry);
-        }
+        let mut entity = T::clone(entry);
+        f(&mut entity);
+
+        Self::check_id(id, &entity)?;
+        Self::validate(&entity)?;
+
+        let now = self.now();
+        entity.on_update(now);
 
         let entry = Entry {
             entity: Arc::new(entity),
         };
 
-        // Re-index the new entry
-        for index in self.indices.values_mut() {
-            index.index(&entry);
+        self.check_indices(&entry)?;
+        self.store(entry, now)?;
+
+        Ok(self.entities.get(id).unwrap())
+    }
+
+    /// Marks the entity with the given ID as deleted, keeping it in the table.
+    /// Soft-deleted entities are skipped by iteration, searches and index lookups,
+    /// but can still be found by ID, until removed with [`Table::purge_deleted`].
+    /// Fails with [`Error::SoftDeleteUnsupported`] without changing anything
+    /// if the entity has no field marked with `#[deleted]`.
+    pub fn soft_delete(&mut self, id: &Id<T>) -> Result<(), Error> {
+        if !T::SOFT_DELETE {
+            return Err(Error::SoftDeleteUnsupported(std::any::type_name::<T>()));
         }
 
-        self.entities.insert(id.clone(), entry);
+        self.modify(id, |entity| entity.set_deleted(true))
+            .map(|_| ())
+    }
+
+    /// Applies `f` to a copy of every entity that is not soft-deleted, then stores the ones
+    /// it changed back in a single pass, re-indexing them and going through [`Entity::on_update`]
+    /// like with [`Table::update`]. Entities left unchanged are not touched.
+    /// Fails without changing anything if `f` changes an entity ID, makes an entity invalid,
+    /// or makes entities share a key of a unique index. As entities are stored one by one,
+    /// swapping unique keys between entities is rejected too.
+    /// Returns the number of entities updated.
+    pub fn for_each_mut<F: FnMut(&mut T)>(&mut self, mut f: F) -> Result<usize, Error>
+    where
+        T: PartialEq,
+    {
+        let mut updated = Vec::new();
+        let now = self.now();
 
-        // Reset search engine on update
-        self.search_engine = Arc::new(Mutex::new(None));
+        for entry in self.entities.iter().filter(|entry| !entry.is_deleted()) {
+            let id = entry.get_id();
+            let mut entity = T::clone(entry);
+            f(&mut entity);
 
-        Ok(self.entities.get(&id).unwrap())
+            if entity == **entry {
+                continue;
+            }
+
+            Self::check_id(id, &entity)?;
+            Self::validate(&entity)?;
+            entity.on_update(now);
+            updated.push(entity);
+        }
+
+        let count = updated.len();
+
+        self.store_all(
+            updated
+                .into_iter()
+                .map(|entity| Entry {
+                    entity: Arc::new(entity),
+                })
+                .collect(),
+            now,
+        )?;
+
+        Ok(count)
     }
 
-    /// Deletes an entity from the table by its ID.
-    pub fn delete(&mut self, id: &Id<T>) -> Result<(), Error> {
-        let Some(existing_entry) = self.entities.remove(id) else {
-            return Err(Error::EntityNotFound(
+    /// Moves every entity of `other` into this table, resolving ID collisions with `policy`.
+    /// Indices and the search engine are maintained for each entity, and the merged copies
+    /// go through [`Entity::on_insert`] or [`Entity::on_update`] like with [`Table::insert`]
+    /// and [`Table::update`].
+    /// With [`MergePolicy::Error`], nothing is merged if any ID collides.
+    /// Nothing is merged either if an entity breaks a unique index.
+    pub fn merge<O: Storage<T>>(
+        &mut self,
+        other: Table<T, O>,
+        policy: MergePolicy,
+    ) -> Result<MergeReport, Error> {
+        if policy == MergePolicy::Error
+            && let Some(id) = other
+                .entities
+                .iter()
+                .map(|entry| entry.get_id())
+                .find(|id| self.entities.contains(id))
+        {
+            return Err(Error::EntityAlreadyExists(
                 id.value().to_string(),
                 std::any::type_name::<T>(),
             ));
-        };
-
-        // Remove the entry from all indices
-        for index in self.indices.values_mut() {
-            index.forget(&existing_entry);
         }
 
-        // Reset search engine on delete
-        self.search_engine = Arc::new(Mutex::new(None));
-        Ok(())
-    }
+        let now = self.now();
+        let mut report = MergeReport::default();
+        let mut entries = Vec::with_capacity(other.entities.len());
 
-    /// Adds an indexer to the table, allowing for indexed queries.
-    pub fn add_index<I: Indexer<Entity = T> + Send + Sync + 'static>(&mut self, mut indexer: I) {
-        let type_id = TypeId::of::<I>();
+        for entry in other.entities.iter() {
+            let exists = self.entities.contains(entry.get_id());
 
-        if self.indices.contains_key(&type_id) {
-            // If the indexer already exists, we can skip adding it again
-            return;
-        }
+            if exists && policy == MergePolicy::KeepSelf {
+                report.conflicted += 1;
+                continue;
+            }
+
+            let mut entity = T::clone(entry);
 
-        for entry in self.entities.values() {
-            indexer.index(entry);
+            if exists {
+                entity.on_update(now);
+                report.overwritten += 1;
+            } else {
+                entity.on_insert(now);
+                report.added += 1;
+            }
+
+            entries.push(Entry {
+                entity: Arc::new(entity),
+            });
         }
 
-        self.indices.insert(type_id, Box::new(indexer));
+        self.store_all(entries, now)?;
+
+        Ok(report)
     }
+}
 
-    /// Finds entries in the table by a specific index key.
-    pub fn get_index<I: Indexer<Entity = T> + 'static>(&self) -> Option<&I> {
-        let type_id = TypeId::of::<I>();
+#[cfg(feature = "bincode")]
+impl<T: Entity + 'static> Table<T> {
+    /// Encodes the table with the standard `bincode` configuration,
+    /// along with the indices declared with `persist`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SnapshotError>
+    where
+        T: bincode::Encode,
+    {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
 
-        if let Some(index) = self.indices.get(&type_id) {
-            // Downcast index from &Box<dyn Indexer<Entity = T>> to &I
-            if let Some(index) = index.as_any().downcast_ref::<I>() {
-                return Some(index);
+    /// Decodes a table encoded with [`Table::to_bytes`].
+    /// Indices have to be added again, and are restored if they were persisted.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError>
+    where
+        T: bincode::Decode<()>,
+    {
+        let (table, _) = bincode::decode_from_slice(bytes, bincode::config::standard())?;
+        Ok(table)
+    }
+
+    /// Saves the table to the file at `path`, replacing it if it exists.
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError>
+    where
+        T: bincode::Encode,
+    {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Loads a table saved with [`Table::save_to_path`].
+    /// Fails with [`SnapshotError::NotFound`] if there is no file at `path`,
+    /// for instance to start with an empty table instead.
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError>
+    where
+        T: bincode::Decode<()>,
+    {
+        let path = path.as_ref();
+
+        match std::fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                Err(SnapshotError::NotFound(path.to_path_buf()))
             }
+            Err(error) => Err(error.into()),
         }
+    }
+}
 
-        None
+impl<T: Entity + PartialEq + 'static, S: Storage<T>> Table<T, S> {
+    /// Updates an existing entity in the table only if it differs from the stored one.
+    /// Returns whether the entity changed; indices and search engine are untouched otherwise.
+    pub fn update_if_changed(&mut self, entity: T) -> Result<bool, Error> {
+        let Some(existing_entry) = self.entities.get(entity.get_id()) else {
+            return Err(Error::EntityNotFound(
+                entity.get_id().value().to_string(),
+                std::any::type_name::<T>(),
+            ));
+        };
+
+        if **existing_entry == entity {
+            return Ok(false);
+        }
+
+        self.update(entity)?;
+        Ok(true)
     }
 }
 
-impl<T: Entity + Searchable> Table<T> {
+impl<T: Entity + Searchable + MaybeSendSync, S: Storage<T>> Table<T, S> {
     /// Searches for entities in the table based on a query string (fuzzy text search).
     pub fn search(&self, query: &str) -> Vec<SearchResult<T>> {
-        let Ok(mut engine) = self.search_engine.lock() else {
+        let Some(state) = self.search_engine() else {
             // If the lock is poisoned, we return an empty search result
             return Vec::new();
         };
 
-        if engine.is_none() {
+        state.engine().search(query)
+    }
+
+    /// Searches for entities in the table, dropping results scoring below `min_score`
+    /// instead of the configured [`SearchConfig::min_score`], without rebuilding the search engine.
+    pub fn search_with_threshold(&self, query: &str, min_score: f32) -> Vec<SearchResult<T>> {
+        let Some(state) = self.search_engine() else {
+            return Vec::new();
+        };
+
+        state.engine().search_with_threshold(query, min_score)
+    }
+
+    /// Searches for entities in the table, keeping only the `k` best results.
+    /// See [`SearchEngine::search_top_k`].
+    pub fn search_top_k(&self, query: &str, k: usize) -> Vec<SearchResult<T>> {
+        let Some(state) = self.search_engine() else {
+            return Vec::new();
+        };
+
+        state.engine().search_top_k(query, k)
+    }
+
+    /// Locks the search engine for reading, building it first if needed
+    /// and applying the changes made to the table since the last search.
+    /// Concurrent searches share the lock, which is only taken for writing to update the engine.
+    /// The returned guard always holds an engine.
+    /// Returns `None` if the lock is poisoned.
+    fn search_engine(&self) -> Option<RwLockReadGuard<'_, SearchState<T>>> {
+        {
+            let state = self.search_engine.read().ok()?;
+
+            if state.engine.is_some() && state.stale.is_empty() {
+                return Some(state);
+            }
+        }
+
+        // Another search may have updated the engine in between, leaving nothing to apply
+        let mut state = self.search_engine.write().ok()?;
+        let SearchState { engine, stale } = &mut *state;
+
+        match engine {
+            // If most entries changed, re-indexing everything is cheaper than patching
+            Some(engine) if stale.len() * 2 > engine.len() => {
+                engine.clear();
+                engine.add_entries(self.searchable_entries());
+            }
+            // Otherwise, replace the changed entries in place
+            Some(engine) => {
+                engine.remove_entries(stale.iter());
+
+                for id in stale.iter() {
+                    if let Some(entry) = self.entities.get(id)
+                        && !entry.is_deleted()
+                    {
+                        engine.add_entry(entry);
+                    }
+                }
+            }
             // If the search engine is not initialized, create a new one
-            *engine = Some(SearchEngine::new(
-                self.entities.values().cloned().collect(),
-                SearchConfig::default(),
-            ));
+            None => {
+                *engine = Some(SearchEngine::new(
+                    self.searchable_entries(),
+                    self.search_config.clone(),
+                ));
+            }
         }
 
-        engine.as_ref().unwrap().search(query)
+        stale.clear();
+
+        Some(RwLockWriteGuard::downgrade(state))
+    }
+
+    /// Returns the entries to index in the search engine, leaving out soft-deleted ones.
+    fn searchable_entries(&self) -> impl Iterator<Item = Entry<T>> + '_ {
+        self.entities
+            .iter()
+            .filter(|entry| !entry.is_deleted())
+            .cloned()
+    }
+
+    /// Searches for entities in the table, reporting why the results are empty
+    /// when the query cannot be used.
+    pub fn search_outcome(&self, query: &str) -> SearchOutcome<T> {
+        let Some(state) = self.search_engine() else {
+            return SearchOutcome {
+                results: Vec::new(),
+                reason: Some(QueryIssue::Unavailable),
+            };
+        };
+
+        state.engine().search_outcome(query)
+    }
+
+    /// Searches for entities in the table, returning only their IDs and scores.
+    pub fn search_ids(&self, query: &str) -> Vec<(Id<T>, f32)> {
+        let Some(state) = self.search_engine() else {
+            return Vec::new();
+        };
+
+        state.engine().search_ids(query)
+    }
+
+    /// Searches for entities in the table matching the words of the query,
+    /// combined following `mode`. See [`SearchEngine::search_terms`].
+    pub fn search_terms(&self, query: &str, mode: QueryMode) -> Vec<SearchResult<T>> {
+        let Some(state) = self.search_engine() else {
+            return Vec::new();
+        };
+
+        state.engine().search_terms(query, mode)
+    }
+
+    /// Searches for entities in the table, attaching the rank of each result.
+    pub fn search_ranked(&self, query: &str) -> Vec<RankedResult<T>> {
+        RankedResult::rank(self.search(query))
+    }
+
+    /// Searches for entities in the table, keeping the `limit` best results.
+    /// See [`SearchEngine::search_top`] for how tied results are handled.
+    pub fn search_top(
+        &self,
+        query: &str,
+        limit: usize,
+        tie_break: TieBreak,
+    ) -> Vec<SearchResult<T>> {
+        let Some(state) = self.search_engine() else {
+            return Vec::new();
+        };
+
+        state.engine().search_top(query, limit, tie_break)
+    }
+
+    /// Finds the entities similar to `entity`, scoring at least `threshold`,
+    /// to detect near-duplicates before inserting it.
+    ///
+    /// The searchable text of the entity (see [`Searchable::write_text`]) is compared as a whole
+    /// to the one of every entity with the Jaro-Winkler similarity (see [`JaroWinklerScorer::compare`]),
+    /// so texts only containing part of it, like empty ones, do not score as perfect matches.
+    /// Results are sorted by descending score, then by ascending entity ID.
+    /// The entity itself is left out of the results if it is already in the table,
+    /// and nothing is found if its text is empty.
+    pub fn find_similar(&self, entity: &T, threshold: f32) -> Vec<SearchResult<T>> {
+        let mut text = String::new();
+        entity.write_text(&mut text);
+
+        let pattern = self.search_config.normalizer.normalize(&text);
+        let Ok(scorer) = JaroWinklerScorer::new(&pattern, &self.search_config) else {
+            return Vec::new();
+        };
+
+        let mut results = self
+            .entities
+            .iter()
+            .filter(|entry| !entry.is_deleted() && entry.get_id() != entity.get_id())
+            .filter_map(|entry| {
+                text.clear();
+                entry.write_text(&mut text);
+
+                let score = scorer.compare(&text);
+                (score >= threshold).then(|| SearchResult::new(entry.clone(), score))
+            })
+            .collect::<Vec<_>>();
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.entry.get_id().cmp(b.entry.get_id()))
+        });
+        results
+    }
+
+    /// Searches for entities in the table, skipping the ones expired at `now`.
+    pub fn search_unexpired(&self, query: &str, now: u64) -> Vec<SearchResult<T>> {
+        let mut results = self.search(query);
+        results.retain(|result| !result.entry.is_expired(now));
+        results
+    }
+}
+
+/// Clones the table, sharing its entities with the clone: entries are reference counted,
+/// so only the map of entries is copied, and changes to either table do not affect the other.
+/// The search configuration, metadata and clock are kept as well, while the search engine
+/// is rebuilt by the clone on its first search.
+/// Indices and callbacks registered with [`Table::on_change`] are not cloned:
+/// indices have to be added again to the clone.
+impl<T: Entity> Clone for Table<T> {
+    fn clone(&self) -> Self {
+        Table {
+            entities: self.entities.clone(),
+            search_engine: Arc::default(),
+            search_config: self.search_config.clone(),
+            indices: HashMap::new(),
+            metadata: self.metadata.clone(),
+            #[cfg(feature = "bincode")]
+            persisted_indices: self.persisted_indices.clone(),
+            observers: Vec::new(),
+            clock: self.clock.clone(),
+        }
     }
 }
 
 impl<T: Entity> Default for Table<T> {
     fn default() -> Self {
         Table {
-            entities: BTreeMap::new(),
-            search_engine: Arc::new(Mutex::new(None)),
+            entities: Entries::in_memory(BTreeMap::new()),
+            search_engine: Arc::default(),
+            search_config: SearchConfig::default(),
             indices: HashMap::new(),
+            metadata: None,
+            #[cfg(feature = "bincode")]
+            persisted_indices: HashMap::new(),
+            observers: Vec::new(),
+            clock: Arc::new(EntryMeta::now),
         }
     }
 }
 
+/// Starts tables saved with a format version. Tables saved before the format was versioned
+/// start with their number of entries instead, which can never be `u64::MAX`.
+#[cfg(feature = "bincode")]
+const FORMAT_TAG: u64 = u64::MAX;
+
+/// The version of the format tables are saved in: their entries, the metadata of the entries
+/// and the persisted indices.
+#[cfg(feature = "bincode")]
+const FORMAT_VERSION: u32 = 1;
+
+/// Fails to decode tables saved in a format version this build does not know.
+#[cfg(feature = "bincode")]
+fn check_format_version(version: u32) -> Result<(), bincode::error::DecodeError> {
+    if version == FORMAT_VERSION {
+        Ok(())
+    } else {
+        Err(bincode::error::DecodeError::Other(
+            "unsupported table format version",
+        ))
+    }
+}
+
 #[cfg(feature = "bincode")]
-impl<T: Entity> bincode::Encode for Table<T>
+impl<T: Entity + 'static> bincode::Encode for Table<T>
 where
     T: bincode::Encode,
 {
@@ -176,7 +1476,21 @@ where
         &self,
         encoder: &mut __E,
     ) -> Result<(), bincode::error::EncodeError> {
-        bincode::Encode::encode(&self.entities, encoder)?;
+        bincode::Encode::encode(&FORMAT_TAG, encoder)?;
+        bincode::Encode::encode(&FORMAT_VERSION, encoder)?;
+        bincode::Encode::encode(self.entities.map(), encoder)?;
+        bincode::Encode::encode(&self.metadata, encoder)?;
+
+        // Indices added since loading take precedence over the ones still waiting
+        let mut persisted_indices = self.persisted_indices.clone();
+
+        for index in self.indices.values() {
+            if let Some(bytes) = index.persist()? {
+                persisted_indices.insert(index.name().to_string(), bytes);
+            }
+        }
+
+        bincode::Encode::encode(&persisted_indices, encoder)?;
         Ok(())
     }
 }
@@ -189,10 +1503,40 @@ where
     fn decode<__D: bincode::de::Decoder<Context = __Context>>(
         decoder: &mut __D,
     ) -> Result<Self, bincode::error::DecodeError> {
+        let tag: u64 = bincode::Decode::decode(decoder)?;
+
+        let (entries, metadata, persisted_indices) = if tag == FORMAT_TAG {
+            check_format_version(bincode::Decode::decode(decoder)?)?;
+
+            (
+                bincode::Decode::decode(decoder)?,
+                bincode::Decode::decode(decoder)?,
+                bincode::Decode::decode(decoder)?,
+            )
+        } else {
+            // Tables saved before the format was versioned only hold their entries,
+            // and the tag read is the number of entries
+            let mut entries = BTreeMap::new();
+            decoder.claim_container_read::<(Id<T>, Entry<T>)>(tag as usize)?;
+
+            for _ in 0..tag {
+                decoder.unclaim_bytes_read(std::mem::size_of::<(Id<T>, Entry<T>)>());
+                let id = bincode::Decode::decode(decoder)?;
+                entries.insert(id, bincode::Decode::decode(decoder)?);
+            }
+
+            (entries, None, HashMap::new())
+        };
+
         Ok(Self {
-            entities: bincode::Decode::decode(decoder)?,
-            search_engine: Arc::new(Mutex::new(None)),
+            entities: Entries::in_memory(entries),
+            search_engine: Arc::default(),
+            search_config: SearchConfig::default(),
             indices: HashMap::new(),
+            metadata,
+            persisted_indices,
+            observers: Vec::new(),
+            clock: Arc::new(EntryMeta::now),
         })
     }
 }
@@ -205,19 +1549,224 @@ where
     fn borrow_decode<__D: bincode::de::BorrowDecoder<'__de, Context = __Context>>(
         decoder: &mut __D,
     ) -> Result<Self, bincode::error::DecodeError> {
+        let tag: u64 = bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?;
+
+        let (entries, metadata, persisted_indices) = if tag == FORMAT_TAG {
+            check_format_version(bincode::BorrowDecode::<'_, __Context>::borrow_decode(
+                decoder,
+            )?)?;
+
+            (
+                bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?,
+                bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?,
+                bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?,
+            )
+        } else {
+            // Tables saved before the format was versioned only hold their entries,
+            // and the tag read is the number of entries
+            let mut entries = BTreeMap::new();
+            decoder.claim_container_read::<(Id<T>, Entry<T>)>(tag as usize)?;
+
+            for _ in 0..tag {
+                decoder.unclaim_bytes_read(std::mem::size_of::<(Id<T>, Entry<T>)>());
+                let id = bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?;
+                let entry = bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?;
+                entries.insert(id, entry);
+            }
+
+            (entries, None, HashMap::new())
+        };
+
         Ok(Self {
-            entities: bincode::BorrowDecode::<'_, __Context>::borrow_decode(decoder)?,
-            search_engine: Arc::new(Mutex::new(None)),
+            entities: Entries::in_memory(entries),
+            search_engine: Arc::default(),
+            search_config: SearchConfig::default(),
             indices: HashMap::new(),
+            metadata,
+            persisted_indices,
+            observers: Vec::new(),
+            clock: Arc::new(EntryMeta::now),
+        })
+    }
+}
+
+/// Tables are serialized as a sequence of their entities; indices, metadata
+/// and the search engine are left out.
+#[cfg(feature = "serde")]
+impl<T: Entity, S: Storage<T>> serde::Serialize for Table<T, S>
+where
+    T: serde::Serialize,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::Serializer,
+    {
+        serializer.collect_seq(self.entities.iter())
+    }
+}
+
+/// Tables are deserialized from a sequence of entities, failing if an ID appears twice.
+/// Like with `bincode`, indices have to be added again after deserializing.
+#[cfg(feature = "serde")]
+impl<'de, T: Entity> serde::Deserialize<'de> for Table<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut entries = BTreeMap::new();
+
+        for entry in Vec::<Entry<T>>::deserialize(deserializer)? {
+            if let Some(entry) = entries.insert(entry.get_id().clone(), entry) {
+                return Err(serde::de::Error::custom(Error::EntityAlreadyExists(
+                    entry.get_id().value().to_string(),
+                    std::any::type_name::<T>(),
+                )));
+            }
+        }
+
+        Ok(Self {
+            entities: Entries::in_memory(entries),
+            ..Self::default()
         })
     }
 }
 
+/// When an entry was created and last updated, as given by the table clock,
+/// in milliseconds since the Unix epoch by default (see [`TableConfig::clock`]).
+/// Recorded by tables created with [`TableConfig::track_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct EntryMeta {
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl EntryMeta {
+    /// Returns the current wall clock time, in milliseconds since the Unix epoch.
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
+
+/// How [`Table::merge`] handles entities whose ID already exists in the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keeps the entity already in the table.
+    KeepSelf,
+    /// Replaces the entity in the table with the merged one.
+    TakeOther,
+    /// Fails the whole merge.
+    Error,
+}
+
+/// How [`Table::delete_referenced`] handles the entries referencing a deleted entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDelete {
+    /// Fails the deletion if any entry still references the entity.
+    Restrict,
+    /// Removes the referencing entries along with the entity.
+    Cascade,
+}
+
+/// The search engine of a table, built on the first search, along with the IDs of the entities
+/// changed since then, to update in the engine on the next search.
+struct SearchState<T: Entity> {
+    engine: Option<SearchEngine<T>>,
+    stale: HashSet<Id<T>>,
+}
+
+impl<T: Entity> SearchState<T> {
+    /// Returns the engine, which is always built when the state is returned by `Table::search_engine`.
+    fn engine(&self) -> &SearchEngine<T> {
+        self.engine.as_ref().unwrap()
+    }
+}
+
+impl<T: Entity> Default for SearchState<T> {
+    fn default() -> Self {
+        SearchState {
+            engine: None,
+            stale: HashSet::new(),
+        }
+    }
+}
+
+/// A change made to a [`Table`], reported to the callbacks registered with [`Table::on_change`].
+#[derive(Debug, Clone)]
+pub enum ChangeEvent<T> {
+    /// A new entity was stored.
+    Inserted(Entry<T>),
+    /// An existing entity was replaced.
+    Updated { old: Entry<T>, new: Entry<T> },
+    /// An entity was removed.
+    Deleted(Entry<T>),
+}
+
+impl<T> ChangeEvent<T> {
+    /// Returns the event for storing `new`, which replaced `old` if any.
+    fn stored(old: Option<Entry<T>>, new: Entry<T>) -> Self {
+        match old {
+            Some(old) => ChangeEvent::Updated { old, new },
+            None => ChangeEvent::Inserted(new),
+        }
+    }
+}
+
+/// Whether [`Table::upsert`] inserted a new entity or updated an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upserted {
+    Inserted,
+    Updated,
+}
+
+/// The side effects of a [`Table::insert_detailed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsertOutcome<T: Entity> {
+    /// The ID of the inserted entity.
+    pub id: Id<T>,
+    /// The name of each index along with the number of keys the entity was indexed under,
+    /// sorted by name.
+    pub index_keys: Vec<(&'static str, usize)>,
+    /// Whether a built search engine has to be updated with the entity on the next search.
+    pub search_invalidated: bool,
+}
+
+/// The outcome of a [`Table::merge`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MergeReport {
+    /// The number of entities added to the table.
+    pub added: usize,
+    /// The number of entities replaced by the merged ones.
+    pub overwritten: usize,
+    /// The number of merged entities ignored because their ID already existed.
+    pub conflicted: usize,
+}
+
+/// A page of entries returned by [`Table::page_after`].
+#[derive(Debug, Clone)]
+pub struct Page<T: Entity> {
+    /// The entries of the page, sorted by ID.
+    pub entries: Vec<Entry<T>>,
+    /// The ID of the last entry of the page, to pass to the next call, or `None` if the page is empty.
+    pub cursor: Option<Id<T>>,
+    /// Whether entries remain after this page.
+    pub has_more: bool,
+}
+
 /// A read-only entry in a table, wrapping an entity.
 /// This has a cheap clone, as it will only clone the Arc.
 /// For mutability, you can call `into_owned` to get an owned version of the entity,
 /// update it and call `update` on the table / database to persist changes.
-#[derive(Debug, PartialOrd, Ord)]
+///
+/// Entries are compared, ordered and hashed by their entity ID, so a `HashSet<Entry<T>>`
+/// deduplicates entries pointing to the same entity. Use [`Entry::ptr_eq`] to compare instances.
+#[derive(Debug)]
 pub struct Entry<T> {
     entity: Arc<T>,
 }
@@ -238,21 +1787,91 @@ impl<T> Clone for Entry<T> {
     }
 }
 
-impl<T> PartialEq for Entry<T> {
-    fn eq(&self, other: &Self) -> bool {
+impl<T> Entry<T> {
+    /// Wraps the entity in a new entry, for storages loading entities from elsewhere.
+    pub fn new(entity: T) -> Self {
+        Entry {
+            entity: Arc::new(entity),
+        }
+    }
+
+    /// Returns whether both entries share the same underlying entity instance.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.entity, &other.entity)
     }
+
+    /// Returns the entity without cloning it if this entry is its only owner,
+    /// or gives the entry back if it is shared, e.g. with the table or another entry.
+    /// See [`Entry::into_owned`] to clone shared entities instead.
+    pub fn try_into_owned(self) -> Result<T, Entry<T>> {
+        Arc::try_unwrap(self.entity).map_err(|entity| Entry { entity })
+    }
+}
+
+impl<T: Entity> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_id() == other.get_id()
+    }
+}
+
+impl<T: Entity> Eq for Entry<T> {}
+
+impl<T: Entity> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Entity> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.get_id().cmp(other.get_id())
+    }
 }
 
-impl<T> Eq for Entry<T> {}
+impl<T: Entity> Hash for Entry<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_id().hash(state);
+    }
+}
 
 impl<T: Clone> Entry<T> {
     /// Clone the internal value, returning an owned version of the entity.
+    /// The entity is moved out without cloning when this entry is its only owner,
+    /// see [`Entry::try_into_owned`] to tell the two cases apart.
     pub fn into_owned(self) -> T {
         Arc::try_unwrap(self.entity).unwrap_or_else(|arc| (*arc).clone())
     }
 }
 
+/// Entries are serialized as their entity.
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Entry<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.entity.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Entry<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(Self {
+            entity: Arc::new(T::deserialize(deserializer)?),
+        })
+    }
+}
+
 #[cfg(feature = "bincode")]
 impl<T> bincode::Encode for Entry<T>
 where
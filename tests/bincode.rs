@@ -0,0 +1,64 @@
+#![cfg(feature = "bincode")]
+
+use bincode::{Decode, Encode};
+use std::collections::BTreeMap;
+use whim::prelude::*;
+
+#[derive(Entity, Encode, Decode, Clone)]
+struct Note {
+    #[id]
+    id: Id<Self>,
+    title: String,
+}
+
+#[index(String -> Note, persist = "notes_by_title")]
+fn NoteTitleIndex(note: &Entry<Note>) -> String {
+    note.title.clone()
+}
+
+fn note(id: &str, title: &str) -> Note {
+    Note {
+        id: Id::new(id),
+        title: title.to_string(),
+    }
+}
+
+#[test]
+fn tables_round_trip() {
+    let mut table = Table::new(TableConfig::new().track_metadata(true));
+    table.insert(note("a", "First")).unwrap();
+    table.insert(note("b", "Second")).unwrap();
+
+    let loaded = Table::<Note>::from_bytes(&table.to_bytes().unwrap()).unwrap();
+
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.find(&Id::new("b")).unwrap().title, "Second");
+    assert!(loaded.metadata(&Id::new("a")).is_some());
+}
+
+#[test]
+fn tables_saved_before_versioning_still_load() {
+    // The first format only held the map of entries
+    let entries = BTreeMap::from([
+        (Id::<Note>::new("a"), Entry::new(note("a", "First"))),
+        (Id::<Note>::new("b"), Entry::new(note("b", "Second"))),
+    ]);
+    let bytes = bincode::encode_to_vec(&entries, bincode::config::standard()).unwrap();
+
+    let loaded = Table::<Note>::from_bytes(&bytes).unwrap();
+
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.find(&Id::new("a")).unwrap().title, "First");
+}
+
+#[test]
+fn persisted_indices_are_restored_by_name() {
+    let mut table = Table::new(TableConfig::new().index(NoteTitleIndex::default()));
+    table.insert(note("a", "First")).unwrap();
+
+    let mut loaded = Table::<Note>::from_bytes(&table.to_bytes().unwrap()).unwrap();
+    loaded.add_index(NoteTitleIndex::default()).unwrap();
+
+    let found = loaded.find_by_index::<NoteTitleIndex>(&"First".to_string());
+    assert_eq!(found.len(), 1);
+}
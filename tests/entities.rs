@@ -0,0 +1,169 @@
+#![cfg(feature = "std")]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use whim::prelude::*;
+
+#[derive(Entity, Clone)]
+struct User {
+    name: String,
+    #[id]
+    id: Id<Self>,
+}
+
+#[derive(Entity, Clone)]
+struct UserId(#[id] Id<Self>);
+
+#[derive(Entity, Clone, PartialEq)]
+struct Tag(String, #[id] Id<Self>, #[deleted] bool, #[updated_at] u64);
+
+#[test]
+fn named_id_fields_are_used() {
+    let mut user = User {
+        name: "Alice".to_string(),
+        id: Id::new("a"),
+    };
+    assert_eq!(user.get_id(), &Id::new("a"));
+
+    user.set_id(Id::new("b"));
+    assert_eq!(user.id, Id::new("b"));
+    assert_eq!(user.name, "Alice");
+}
+
+#[test]
+fn positional_id_fields_are_used() {
+    let mut user = UserId(Id::new("a"));
+    assert_eq!(user.get_id(), &Id::new("a"));
+
+    user.set_id(Id::new("b"));
+    assert_eq!(user.0, Id::new("b"));
+
+    let mut table = Table::<UserId>::default();
+    table.insert(UserId(Id::new("a"))).unwrap();
+    assert!(table.find(&Id::new("a")).is_some());
+}
+
+#[test]
+fn positional_fields_are_used_after_the_first_one() {
+    let mut tag = Tag("rust".to_string(), Id::new("t"), false, 0);
+    assert_eq!(tag.get_id(), &Id::new("t"));
+
+    tag.set_deleted(true);
+    tag.on_update(42);
+    assert!(tag.is_deleted());
+    assert_eq!(tag.3, 42);
+    assert_eq!(tag.0, "rust");
+}
+
+#[test]
+fn soft_deleting_without_a_deleted_field_fails() {
+    let time = Arc::new(AtomicU64::new(1));
+    let clock = time.clone();
+    let mut table = Table::new(
+        TableConfig::new()
+            .track_metadata(true)
+            .clock(move || clock.load(Ordering::Relaxed)),
+    );
+    table
+        .insert(User {
+            name: "Alice".to_string(),
+            id: Id::new("a"),
+        })
+        .unwrap();
+    time.store(2, Ordering::Relaxed);
+
+    assert!(matches!(
+        table.soft_delete(&Id::new("a")),
+        Err(Error::SoftDeleteUnsupported(_))
+    ));
+    assert_eq!(table.metadata(&Id::new("a")).unwrap().updated_at, 1);
+}
+
+#[test]
+fn for_each_mut_only_updates_changed_entities() {
+    let time = Arc::new(AtomicU64::new(1));
+    let clock = time.clone();
+    let mut table = Table::new(TableConfig::new().clock(move || clock.load(Ordering::Relaxed)));
+    for (name, id) in [("rust", "a"), ("go", "b"), ("zig", "c")] {
+        table
+            .insert(Tag(name.to_string(), Id::new(id), false, 0))
+            .unwrap();
+    }
+    table.soft_delete(&Id::new("c")).unwrap();
+    time.store(2, Ordering::Relaxed);
+
+    let mut visited = Vec::new();
+    let updated = table
+        .for_each_mut(|tag| {
+            visited.push(tag.0.clone());
+            if tag.0 == "rust" {
+                tag.0 = "rustlang".to_string();
+            }
+        })
+        .unwrap();
+
+    assert_eq!(updated, 1);
+    assert_eq!(visited, ["rust", "go"]);
+    assert_eq!(table.find(&Id::new("a")).unwrap().3, 2);
+    assert_eq!(table.find(&Id::new("b")).unwrap().3, 1);
+    assert_eq!(table.find(&Id::new("c")).unwrap().0, "zig");
+}
+
+#[derive(Entity, Clone)]
+struct Post {
+    #[id]
+    id: Id<Self>,
+    #[created_at]
+    created_at: u64,
+    #[updated_at]
+    updated_at: u64,
+}
+
+fn post(id: &str) -> Post {
+    Post {
+        id: Id::new(id),
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+#[test]
+fn timestamps_match_the_metadata() {
+    // Every read of the clock returns a later time
+    let time = Arc::new(AtomicU64::new(0));
+    let clock = time.clone();
+    let mut table = Table::new(
+        TableConfig::new()
+            .track_metadata(true)
+            .clock(move || clock.fetch_add(1, Ordering::Relaxed) + 1),
+    );
+
+    let created_at = table.insert(post("a")).unwrap().created_at;
+    let meta = table.metadata(&Id::new("a")).unwrap();
+    assert_eq!(meta.created_at, created_at);
+
+    let updated_at = table.update(post("a")).unwrap().updated_at;
+    let meta = table.metadata(&Id::new("a")).unwrap();
+    assert_eq!(meta.updated_at, updated_at);
+}
+
+#[test]
+fn merged_entities_are_timestamped() {
+    let time = Arc::new(AtomicU64::new(1));
+    let clock = time.clone();
+    let mut table = Table::new(TableConfig::new().clock(move || clock.load(Ordering::Relaxed)));
+    table.insert(post("a")).unwrap();
+
+    let mut other = Table::new(TableConfig::new().clock(|| 2));
+    other.insert(post("a")).unwrap();
+    other.insert(post("b")).unwrap();
+
+    time.store(3, Ordering::Relaxed);
+    table.merge(other, MergePolicy::TakeOther).unwrap();
+
+    // The overwritten entity is updated, the added one inserted
+    let a = table.find(&Id::new("a")).unwrap();
+    assert_eq!((a.created_at, a.updated_at), (2, 3));
+    let b = table.find(&Id::new("b")).unwrap();
+    assert_eq!((b.created_at, b.updated_at), (3, 3));
+}
@@ -0,0 +1,188 @@
+#![cfg(feature = "std")]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use whim::prelude::*;
+
+#[derive(Entity, Clone, PartialEq)]
+struct Note {
+    #[id]
+    id: Id<Self>,
+    created_by: String,
+    created_at: u64,
+}
+
+#[index((String, u64) -> Note)]
+fn NoteAuthorDateIndex(note: &Entry<Note>) -> (String, u64) {
+    (note.created_by.clone(), note.created_at)
+}
+
+#[index((String, u64) -> Note)]
+fn NoteAuthorDatesIndex(note: &Entry<Note>) -> Vec<(String, u64)> {
+    vec![
+        (note.created_by.clone(), note.created_at),
+        (note.created_by.clone(), 0),
+    ]
+}
+
+#[index(String -> Note, unique)]
+fn NoteAuthorIndex(note: &Entry<Note>) -> String {
+    note.created_by.clone()
+}
+
+#[derive(Entity, Clone)]
+struct Task {
+    #[id]
+    id: Id<Self>,
+    owner: String,
+    #[deleted]
+    deleted: bool,
+}
+
+#[index(String -> Task, unique)]
+fn TaskOwnerIndex(task: &Entry<Task>) -> String {
+    task.owner.clone()
+}
+
+fn note(id: &str, created_by: &str, created_at: u64) -> Note {
+    Note {
+        id: Id::new(id),
+        created_by: created_by.to_string(),
+        created_at,
+    }
+}
+
+fn table() -> Table<Note> {
+    let mut table = Table::new(
+        TableConfig::new()
+            .index(NoteAuthorDateIndex::default())
+            .index(NoteAuthorDatesIndex::default()),
+    );
+    table.insert(note("a", "alice", 2)).unwrap();
+    table.insert(note("b", "alice", 1)).unwrap();
+    table.insert(note("c", "bob", 1)).unwrap();
+    table
+}
+
+#[test]
+fn tuple_keys_are_found() {
+    let table = table();
+    let index = table.get_index::<NoteAuthorDateIndex>().unwrap();
+
+    let found = index.find(&("alice".to_string(), 1));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, Id::new("b"));
+
+    assert!(index.find(&("bob".to_string(), 2)).is_empty());
+    assert_eq!(
+        table
+            .find_by_index::<NoteAuthorDateIndex>(&("bob".to_string(), 1))
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn tuple_keys_follow_the_order_of_their_fields() {
+    let table = table();
+    let index = table.get_index::<NoteAuthorDateIndex>().unwrap();
+
+    // The entries of a user, sorted by date
+    let found = index.find_range(("alice".to_string(), 0)..("alice".to_string(), u64::MAX));
+    let ids = found.iter().map(|note| note.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![Id::new("b"), Id::new("a")]);
+}
+
+#[test]
+fn tuple_key_generators_may_return_several_keys() {
+    let table = table();
+    let index = table.get_index::<NoteAuthorDatesIndex>().unwrap();
+
+    assert_eq!(index.count(&("alice".to_string(), 0)), 2);
+    assert_eq!(index.count(&("alice".to_string(), 2)), 1);
+}
+
+#[test]
+fn unique_indices_are_rejected_over_shared_keys() {
+    let mut table = table();
+
+    assert!(matches!(
+        table.add_index(NoteAuthorIndex::default()),
+        Err(Error::UniqueViolation(_, _))
+    ));
+    assert!(table.get_index::<NoteAuthorIndex>().is_none());
+
+    table.delete(&Id::new("b")).unwrap();
+    assert!(table.add_index(NoteAuthorIndex::default()).unwrap());
+}
+
+#[test]
+fn failed_batches_leave_the_table_unchanged() {
+    let time = Arc::new(AtomicU64::new(1));
+    let clock = time.clone();
+    let mut table = Table::new(
+        TableConfig::new()
+            .track_metadata(true)
+            .clock(move || clock.load(Ordering::Relaxed))
+            .index(NoteAuthorIndex::default()),
+    );
+    table.insert(note("a", "alice", 1)).unwrap();
+    table.insert(note("b", "bob", 1)).unwrap();
+    time.store(2, Ordering::Relaxed);
+
+    // "c" is stored before "d" is rejected, then removed again
+    let result = table.insert_many(vec![note("c", "carol", 2), note("d", "alice", 2)]);
+    assert!(matches!(result, Err(Error::UniqueViolation(_, _))));
+    assert!(table.find(&Id::new("c")).is_none());
+    assert!(table.metadata(&Id::new("c")).is_none());
+
+    // Only "b" changes, and is rejected then restored
+    let result = table.for_each_mut(|note| note.created_by = "alice".to_string());
+    assert!(matches!(result, Err(Error::UniqueViolation(_, _))));
+    assert_eq!(table.find(&Id::new("b")).unwrap().created_by, "bob");
+
+    for id in ["a", "b"] {
+        let meta = table.metadata(&Id::new(id)).unwrap();
+        assert_eq!((meta.created_at, meta.updated_at), (1, 1));
+    }
+
+    let index = table.get_index::<NoteAuthorIndex>().unwrap();
+    assert_eq!(index.count(&"alice".to_string()), 1);
+    assert_eq!(index.count(&"bob".to_string()), 1);
+    assert_eq!(index.count(&"carol".to_string()), 0);
+}
+
+#[test]
+fn soft_deleted_entries_are_left_out_of_indices() {
+    let mut table = Table::new(TableConfig::new().index(TaskOwnerIndex::default()));
+    table
+        .insert(Task {
+            id: Id::new("a"),
+            owner: "alice".to_string(),
+            deleted: false,
+        })
+        .unwrap();
+
+    table.soft_delete(&Id::new("a")).unwrap();
+
+    let index = table.get_index::<TaskOwnerIndex>().unwrap();
+    assert!(index.find(&"alice".to_string()).is_empty());
+    assert!(!index.contains_key(&"alice".to_string()));
+    assert_eq!(index.keys().count(), 0);
+    assert!(table.find(&Id::new("a")).is_some());
+
+    // The key of a soft-deleted entry can be used again
+    table
+        .insert(Task {
+            id: Id::new("b"),
+            owner: "alice".to_string(),
+            deleted: false,
+        })
+        .unwrap();
+
+    let index = table.get_index::<TaskOwnerIndex>().unwrap();
+    assert_eq!(
+        index.find_one(&"alice".to_string()).unwrap().id,
+        Id::new("b")
+    );
+}
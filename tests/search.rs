@@ -0,0 +1,114 @@
+#![cfg(feature = "std")]
+
+use whim::prelude::*;
+use whim::search::{SearchConfig, TieBreak};
+
+#[derive(Entity, Searchable)]
+struct Article {
+    #[id]
+    id: Id<Self>,
+    #[search]
+    title: String,
+}
+
+#[derive(Entity, Searchable)]
+struct Note {
+    #[id]
+    id: Id<Self>,
+    #[search(weight = 2.0)]
+    title: String,
+    #[search]
+    content: String,
+}
+
+#[test]
+fn field_weights_can_exceed_one() {
+    let mut table = Table::new(TableConfig::new().search(SearchConfig::default()));
+    table
+        .insert(Note {
+            id: Id::new("a"),
+            title: "groceries".to_string(),
+            content: "milk and eggs".to_string(),
+        })
+        .unwrap();
+
+    let results = table.search("groceries");
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].score, 2.0);
+}
+
+#[test]
+fn phrases_with_stop_words_match_exactly() {
+    let config = SearchConfig {
+        stop_words: ["of", "the"].map(String::from).into(),
+        ..SearchConfig::default()
+    };
+    let mut table = Table::new(TableConfig::new().search(config));
+    let title = "Reviews: state of the art";
+    table
+        .insert(Article {
+            id: Id::new("a"),
+            title: title.to_string(),
+        })
+        .unwrap();
+
+    let results = table.search("State of the Art");
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        &title[results[0].fields[0].range.clone().unwrap()],
+        "state of the art"
+    );
+}
+
+#[test]
+fn top_results_keep_exact_ties_with_the_last_one() {
+    let mut table = Table::new(TableConfig::new().search(SearchConfig::default()));
+    for (id, title) in [("d", "rust"), ("b", "rust"), ("c", "rust"), ("a", "rest")] {
+        table
+            .insert(Article {
+                id: Id::new(id),
+                title: title.to_string(),
+            })
+            .unwrap();
+    }
+
+    let ids = |tie_break| {
+        table
+            .search_top("rust", 2, tie_break)
+            .iter()
+            .map(|result| result.entry.get_id().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids(TieBreak::ById), ["b", "c"]);
+    assert_eq!(ids(TieBreak::IncludeTies), ["b", "c", "d"]);
+}
+
+#[test]
+fn similar_entities_are_compared_on_their_whole_text() {
+    let mut table = Table::new(TableConfig::new().search(SearchConfig::default()));
+    for (id, title) in [
+        ("a", ""),
+        ("b", "Reviews"),
+        ("c", "Reviews: state of the arts"),
+    ] {
+        table
+            .insert(Article {
+                id: Id::new(id),
+                title: title.to_string(),
+            })
+            .unwrap();
+    }
+
+    let probe = Article {
+        id: Id::new("probe"),
+        title: "Reviews: state of the art".to_string(),
+    };
+    let results = table.find_similar(&probe, 0.9);
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].entry.get_id(), &Id::new("c"));
+    assert!(results[0].score < 1.0);
+}
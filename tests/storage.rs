@@ -0,0 +1,168 @@
+#![cfg(feature = "std")]
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use whim::prelude::*;
+use whim::storage::{Storage, StorageIter};
+
+#[derive(Entity, Clone)]
+struct Note {
+    #[id]
+    id: Id<Self>,
+    title: String,
+}
+
+#[index(String -> Note)]
+fn NoteTitleIndex(note: &Entry<Note>) -> String {
+    note.title.clone()
+}
+
+#[index(String -> Note, unique)]
+fn NoteUniqueTitleIndex(note: &Entry<Note>) -> String {
+    note.title.clone()
+}
+
+fn note(id: &str, title: &str) -> Note {
+    Note {
+        id: Id::new(id),
+        title: title.to_string(),
+    }
+}
+
+/// Stands for a storage outside of memory, refusing to write the entities titled "fail".
+#[derive(Default)]
+struct FlakyStorage {
+    entries: BTreeMap<Id<Note>, Entry<Note>>,
+    unreadable: bool,
+}
+
+impl Storage<Note> for FlakyStorage {
+    type Error = String;
+
+    fn get(&self, id: &Id<Note>) -> Result<Option<Entry<Note>>, String> {
+        Ok(self.entries.get(id).cloned())
+    }
+
+    fn insert(&mut self, entry: Entry<Note>) -> Result<Option<Entry<Note>>, String> {
+        if entry.title == "fail" {
+            return Err("disk full".to_string());
+        }
+
+        Ok(self.entries.insert(entry.id.clone(), entry))
+    }
+
+    fn remove(&mut self, id: &Id<Note>) -> Result<Option<Entry<Note>>, String> {
+        Ok(self.entries.remove(id))
+    }
+
+    fn len(&self) -> Result<usize, String> {
+        Ok(self.entries.len())
+    }
+
+    fn iter(&self) -> StorageIter<'_, Note, String> {
+        if self.unreadable {
+            return Box::new(std::iter::once(Err("corrupted".to_string())));
+        }
+
+        Box::new(self.entries.values().cloned().map(Ok))
+    }
+
+    fn range(&self, range: (Bound<&Id<Note>>, Bound<&Id<Note>>)) -> StorageIter<'_, Note, String> {
+        Box::new(
+            self.entries
+                .range::<Id<Note>, _>(range)
+                .map(|(_, entry)| Ok(entry.clone())),
+        )
+    }
+}
+
+fn stored(entries: &[Note]) -> FlakyStorage {
+    FlakyStorage {
+        entries: entries
+            .iter()
+            .map(|note| (note.id.clone(), Entry::new(note.clone())))
+            .collect(),
+        unreadable: false,
+    }
+}
+
+#[test]
+fn tables_load_and_index_stored_entries() {
+    let storage = stored(&[note("a", "First")]);
+    let table =
+        Table::with_storage(storage, TableConfig::new().index(NoteTitleIndex::default())).unwrap();
+
+    assert_eq!(table.find(&Id::new("a")).unwrap().title, "First");
+    assert_eq!(
+        table
+            .find_by_index::<NoteTitleIndex>(&"First".to_string())
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn changes_are_written_to_the_storage() {
+    let mut table = Table::with_storage(FlakyStorage::default(), TableConfig::new()).unwrap();
+    table.insert(note("a", "First")).unwrap();
+    table.insert(note("b", "Second")).unwrap();
+    table.delete(&Id::new("a")).unwrap();
+
+    let storage = table.storage();
+    assert_eq!(storage.len(), Ok(1));
+    assert_eq!(storage.get(&Id::new("b")).unwrap().unwrap().title, "Second");
+}
+
+#[test]
+fn storage_failures_leave_the_table_unchanged() {
+    let mut table = Table::with_storage(
+        FlakyStorage::default(),
+        TableConfig::new().index(NoteTitleIndex::default()),
+    )
+    .unwrap();
+
+    assert!(matches!(
+        table.insert(note("a", "fail")),
+        Err(Error::Storage(message, _)) if message == "disk full"
+    ));
+    assert!(table.find(&Id::new("a")).is_none());
+
+    // "b" is written before "c" fails, then removed again
+    assert!(matches!(
+        table.insert_many(vec![note("b", "Second"), note("c", "fail")]),
+        Err(Error::Storage(_, _))
+    ));
+    assert!(table.is_empty());
+    assert_eq!(table.storage().len(), Ok(0));
+    assert!(
+        table
+            .find_by_index::<NoteTitleIndex>(&"Second".to_string())
+            .is_empty()
+    );
+}
+
+#[test]
+fn unreadable_storages_are_reported() {
+    let storage = FlakyStorage {
+        unreadable: true,
+        ..FlakyStorage::default()
+    };
+
+    assert!(matches!(
+        Table::with_storage(storage, TableConfig::new()),
+        Err(Error::Storage(message, _)) if message == "corrupted"
+    ));
+}
+
+#[test]
+fn stored_entries_are_checked_against_unique_indices() {
+    let storage = stored(&[note("a", "Same"), note("b", "Same")]);
+
+    assert!(matches!(
+        Table::with_storage(
+            storage,
+            TableConfig::new().index(NoteUniqueTitleIndex::default())
+        ),
+        Err(Error::UniqueViolation(_, _))
+    ));
+}